@@ -106,7 +106,7 @@ fn write_tests_header(outfile: &mut fs::File) {
 
 use clap::Parser;
 
-use sgf_render::{{Goban, RenderArgs, OutputFormat, svg}};
+use sgf_render::{{parse_collection, Goban, GobanOptions, RenderArgs, OutputFormat, svg}};
 "#,
     )
     .unwrap();
@@ -124,8 +124,10 @@ fn write_test(outfile: &mut fs::File, dir: &fs::DirEntry) {
 #[test]
 fn {test_name}() {{
     let mut arguments = shell_words::split(include_str!(r"{path}{separator}options.txt")).unwrap();
-    if let Some(i) = arguments.iter().position(|s| s == "--custom-style") {{
-        arguments[i + 1] = format!(r"{path}{separator}{{}}", arguments[i + 1]);
+    for flag in ["--custom-style", "--analysis"] {{
+        if let Some(i) = arguments.iter().position(|s| s == flag) {{
+            arguments[i + 1] = format!(r"{path}{separator}{{}}", arguments[i + 1]);
+        }}
     }}
     arguments.insert(0, "sgf-render".to_string());
     let render_args = RenderArgs::parse_from(&arguments);
@@ -133,7 +135,15 @@ fn {test_name}() {{
     let input = include_str!(r"{path}{separator}input.sgf");
     let expected = include_str!(r"{path}{separator}output.svg");
 
-    let goban = Goban::from_sgf(input, &options.node_description, true).unwrap();
+    let collection = parse_collection(input, true).unwrap();
+    let goban_options = GobanOptions {{
+        check_rules: true,
+        sgf_analysis: options.draw_sgf_analysis,
+    }};
+    let goban = Goban::from_collection(&collection, std::slice::from_ref(&options.node_description), &goban_options)
+        .unwrap()
+        .pop()
+        .unwrap();
     let svg = svg::render(&goban, &options).unwrap();
     let mut buffer: Vec<u8> = vec![];
     svg.write_to(&mut buffer).unwrap();
@@ -0,0 +1,94 @@
+//! Candidate moves listed in a node's comment, one per line, e.g. `Q16: 55.2% 1200 visits +1.5`.
+//!
+//! Each line starts with a point in GTP coordinates and continues with any of a winrate
+//! percentage, a visit count and a signed score lead. Lines with anything else are ignored, so
+//! comments can mix candidates with prose.
+
+use sgf_parse::{go::Prop, SgfNode};
+
+use super::{parse_gtp_point, Analysis, Candidate};
+
+pub fn parse(sgf_node: &SgfNode<Prop>, size: (u8, u8)) -> Option<Analysis> {
+    let comment = sgf_node.properties().find_map(|prop| match prop {
+        Prop::C(comment) => Some(comment.to_string()),
+        _ => None,
+    })?;
+    let candidates: Vec<Candidate> = comment
+        .lines()
+        .filter_map(|line| parse_line(line, size))
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(Analysis {
+        candidates,
+        ..Default::default()
+    })
+}
+
+fn parse_line(line: &str, size: (u8, u8)) -> Option<Candidate> {
+    let tokens: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+        .collect();
+    let (point, mut fields) = tokens.split_first()?;
+    let mut candidate = Candidate {
+        point: parse_gtp_point(point.trim_end_matches(':'), size)?,
+        winrate: None,
+        score_lead: None,
+        visits: None,
+    };
+    loop {
+        fields = match fields {
+            [] => break,
+            [visits, "visits", rest @ ..] => {
+                candidate.visits = Some(visits.parse().ok()?);
+                rest
+            }
+            [field, rest @ ..] => {
+                if let Some(percentage) = field.strip_suffix('%') {
+                    candidate.winrate = Some(percentage.parse::<f64>().ok()? / 100.0);
+                } else if field.starts_with(['+', '-']) {
+                    candidate.score_lead = Some(field.parse().ok()?);
+                } else {
+                    return None;
+                }
+                rest
+            }
+        };
+    }
+    if candidate.winrate.is_none() && candidate.visits.is_none() {
+        return None;
+    }
+    Some(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn candidate_lines() {
+        let sgf = "(;SZ[19]C[Top moves:\n\
+                   Q16: 55.2% 1200 visits +1.5\n\
+                   D4 48.0%, -0.5\n\
+                   C3 is an invasion worth 10 points\n\
+                   R4 300 visits])";
+        let collection = sgf_parse::go::parse(sgf).unwrap();
+        let analysis = parse(&collection[0], (19, 19)).unwrap();
+        let points: Vec<_> = analysis.candidates.iter().map(|c| c.point).collect();
+        assert_eq!(points, vec![(15, 3), (3, 15), (16, 15)]);
+        assert_eq!(analysis.candidates[0].winrate, Some(0.552));
+        assert_eq!(analysis.candidates[0].visits, Some(1200));
+        assert_eq!(analysis.candidates[0].score_lead, Some(1.5));
+        assert_eq!(analysis.candidates[1].visits, None);
+        assert_eq!(analysis.candidates[2].winrate, None);
+    }
+
+    #[test]
+    fn plain_comment() {
+        let sgf = "(;SZ[19]C[A4 would be better here.])";
+        let collection = sgf_parse::go::parse(sgf).unwrap();
+        assert!(parse(&collection[0], (19, 19)).is_none());
+    }
+}
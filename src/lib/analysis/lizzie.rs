@@ -0,0 +1,92 @@
+//! Lizzie's `LZ` property, written for both Leela Zero and KataGo analysis.
+//!
+//! The first line of the value summarizes the position and the second holds the engine's `info`
+//! output for each candidate, e.g.
+//! `move Q16 visits 1200 winrate 5520 scoreMean 1.5 order 0 pv Q16 D4 info move D4 ...`.
+
+use sgf_parse::{go::Prop, SgfNode};
+
+use super::{normalize_winrate, parse_gtp_point, Analysis, Candidate};
+
+pub fn parse(sgf_node: &SgfNode<Prop>, size: (u8, u8)) -> Option<Analysis> {
+    let value = sgf_node.properties().find_map(|prop| match prop {
+        Prop::Unknown(identifier, values) if identifier == "LZ" => values.first(),
+        _ => None,
+    })?;
+    let info = value.lines().nth(1)?;
+    let tokens: Vec<&str> = info.split_whitespace().collect();
+    let mut candidates: Vec<(Option<u64>, Candidate)> = tokens
+        .split(|&token| token == "info")
+        .filter_map(|tokens| parse_candidate(tokens, size))
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    candidates.sort_by_key(|&(order, _)| order.unwrap_or(u64::MAX));
+    Some(Analysis {
+        candidates: candidates
+            .into_iter()
+            .map(|(_, candidate)| candidate)
+            .collect(),
+        ..Default::default()
+    })
+}
+
+/// Parses the key value pairs of a single `info` entry, returning its order and candidate.
+fn parse_candidate(tokens: &[&str], size: (u8, u8)) -> Option<(Option<u64>, Candidate)> {
+    let mut point = None;
+    let mut order = None;
+    let mut candidate = Candidate {
+        point: (0, 0),
+        winrate: None,
+        score_lead: None,
+        visits: None,
+    };
+    for pair in tokens.chunks(2) {
+        match pair {
+            // The principal variation runs to the end of the entry.
+            ["pv", ..] => break,
+            ["move", value] => point = parse_gtp_point(value, size),
+            ["visits", value] => candidate.visits = value.parse().ok(),
+            ["winrate", value] => candidate.winrate = value.parse().ok().map(normalize_winrate),
+            ["scoreMean" | "scoreLead", value] => candidate.score_lead = value.parse().ok(),
+            ["order", value] => order = value.parse().ok(),
+            _ => {}
+        }
+    }
+    candidate.point = point?;
+    Some((order, candidate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn leela_zero() {
+        let sgf = "(;SZ[19];B[pd]LZ[0.17 47.3 2400\n\
+                   move D4 visits 300 winrate 4650 order 1 pv D4 Q16 \
+                   info move D16 visits 1800 winrate 4731 order 0 pv D16 Q16 D4 \
+                   info move pass visits 2 winrate 100 order 2 pv pass])";
+        let collection = sgf_parse::go::parse(sgf).unwrap();
+        let node = &collection[0].children[0];
+        let analysis = parse(node, (19, 19)).unwrap();
+        assert_eq!(analysis.candidates.len(), 2);
+        assert_eq!(analysis.candidates[0].point, (3, 3));
+        assert_eq!(analysis.candidates[0].visits, Some(1800));
+        assert_eq!(analysis.candidates[0].winrate, Some(0.4731));
+        assert_eq!(analysis.candidates[1].point, (3, 15));
+        assert_eq!(analysis.candidates[1].score_lead, None);
+    }
+
+    #[test]
+    fn katago() {
+        let sgf = "(;SZ[9]LZ[KataGo 52.0 500 0.5 3.1 7.0\n\
+                   move E5 visits 500 winrate 0.52 scoreMean 0.5 order 0 pv E5 C4])";
+        let collection = sgf_parse::go::parse(sgf).unwrap();
+        let analysis = parse(&collection[0], (9, 9)).unwrap();
+        assert_eq!(analysis.candidates[0].point, (4, 4));
+        assert_eq!(analysis.candidates[0].winrate, Some(0.52));
+        assert_eq!(analysis.candidates[0].score_lead, Some(0.5));
+    }
+}
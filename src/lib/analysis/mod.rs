@@ -0,0 +1,280 @@
+mod comment;
+mod lizzie;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use sgf_parse::{go::Prop, SgfNode};
+
+use crate::args::parse_point;
+use crate::errors::UsageError;
+use crate::render::Transform;
+
+/// Engine analysis of a position: ownership estimates and candidate moves.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Analysis {
+    /// Ownership of each point from -1 (white) to 1 (black).
+    pub ownership: HashMap<(u8, u8), f64>,
+    pub candidates: Vec<Candidate>,
+}
+
+/// Reads analysis from an SGF node for a board of the given size, or returns `None` if the node
+/// holds no analysis in the parser's format.
+type SgfParser = fn(&SgfNode<Prop>, (u8, u8)) -> Option<Analysis>;
+
+/// Parsers for engine analysis stored in an SGF node, tried in order.
+static SGF_PARSERS: &[SgfParser] = &[lizzie::parse, comment::parse];
+
+/// A candidate move with the engine's evaluation of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub point: (u8, u8),
+    /// Winrate for the player to move, from 0 to 1.
+    pub winrate: Option<f64>,
+    /// Score lead for the player to move.
+    pub score_lead: Option<f64>,
+    pub visits: Option<u64>,
+}
+
+#[derive(serde::Deserialize)]
+struct AnalysisData {
+    #[serde(default)]
+    ownership: Option<OwnershipData>,
+    #[serde(default)]
+    candidates: Vec<CandidateData>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum OwnershipData {
+    Points(HashMap<String, f64>),
+    Rows(Vec<Vec<f64>>),
+    Flat(Vec<f64>),
+}
+
+#[derive(serde::Deserialize)]
+struct CandidateData {
+    point: String,
+    winrate: Option<f64>,
+    score_lead: Option<f64>,
+    visits: Option<u64>,
+}
+
+impl Analysis {
+    /// Reads analysis from JSON.
+    ///
+    /// Ownership may be given as an object mapping SGF points to values, as an array of rows from
+    /// the top of the board, or as a single array of values row by row for a square board.
+    pub fn from_json(data: &str) -> Result<Self, UsageError> {
+        let data: AnalysisData =
+            serde_json::from_str(data).map_err(|e| UsageError::AnalysisReadError(e.into()))?;
+        let ownership = match data.ownership {
+            None => HashMap::new(),
+            Some(OwnershipData::Points(values)) => values
+                .into_iter()
+                .map(|(point, value)| Ok((parse_point(&point)?, value)))
+                .collect::<Result<_, UsageError>>()?,
+            Some(OwnershipData::Rows(rows)) => grid_ownership(&rows),
+            Some(OwnershipData::Flat(values)) => {
+                let size = (values.len() as f64).sqrt() as usize;
+                if size * size != values.len() {
+                    return Err(UsageError::AnalysisReadError(
+                        "Ownership array isn't for a square board".into(),
+                    ));
+                }
+                let rows: Vec<Vec<f64>> = values.chunks(size).map(<[f64]>::to_vec).collect();
+                grid_ownership(&rows)
+            }
+        };
+        let candidates = data
+            .candidates
+            .into_iter()
+            .map(|candidate| {
+                Ok(Candidate {
+                    point: parse_point(&candidate.point)?,
+                    winrate: candidate.winrate,
+                    score_lead: candidate.score_lead,
+                    visits: candidate.visits,
+                })
+            })
+            .collect::<Result<_, UsageError>>()?;
+        Ok(Analysis {
+            ownership: clamped(ownership),
+            candidates,
+        })
+    }
+
+    /// Reads engine analysis stored in an SGF node for a board of the given size.
+    ///
+    /// Candidates are listed from the engine's most to least preferred move.
+    pub fn from_sgf_node(sgf_node: &SgfNode<Prop>, size: (u8, u8)) -> Option<Self> {
+        SGF_PARSERS.iter().find_map(|parse| parse(sgf_node, size))
+    }
+
+    /// Reads ownership from CSV lines of the form `POINT,VALUE`.
+    ///
+    /// Blank lines, lines starting with '#' and a `point,value` header are skipped.
+    pub fn from_csv(data: &str) -> Result<Self, UsageError> {
+        let mut ownership = HashMap::new();
+        for (i, line) in data.lines().map(str::trim).enumerate() {
+            if line.is_empty() || line.starts_with('#') || i == 0 && line == "point,value" {
+                continue;
+            }
+            let error = || UsageError::AnalysisReadError(format!("Invalid line '{line}'").into());
+            let (point, value) = line.split_once(',').ok_or_else(error)?;
+            let value: f64 = value.trim().parse().map_err(|_| error())?;
+            ownership.insert(parse_point(point.trim())?, value);
+        }
+        Ok(Analysis {
+            ownership: clamped(ownership),
+            candidates: vec![],
+        })
+    }
+
+    /// Returns a copy of the analysis with the transform applied to every point.
+    ///
+    /// Points which aren't on a board of the given size (e.g. from a file for a larger board) are
+    /// dropped.
+    pub fn transformed(&self, transform: &Transform, size: (u8, u8)) -> Self {
+        let on_board = |p: (u8, u8)| p.0 < size.0 && p.1 < size.1;
+        let point = |p: (u8, u8)| transform.point(p, size);
+        Analysis {
+            ownership: self
+                .ownership
+                .iter()
+                .filter(|(&p, _)| on_board(p))
+                .map(|(&p, &value)| (point(p), value))
+                .collect(),
+            candidates: self
+                .candidates
+                .iter()
+                .filter(|candidate| on_board(candidate.point))
+                .map(|candidate| Candidate {
+                    point: point(candidate.point),
+                    ..candidate.clone()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Parses a point in GTP coordinates (e.g. 'Q16'), where columns skip 'I' and rows count up from
+/// the bottom of the board.
+fn parse_gtp_point(text: &str, size: (u8, u8)) -> Option<(u8, u8)> {
+    let mut chars = text.chars();
+    let column = chars.next()?.to_ascii_uppercase();
+    let x = match column {
+        'A'..='H' => column as u8 - b'A',
+        'J'..='Z' => column as u8 - b'A' - 1,
+        _ => return None,
+    };
+    let row: u8 = chars.as_str().parse().ok()?;
+    if x >= size.0 || row == 0 || row > size.1 {
+        return None;
+    }
+    Some((x, size.1 - row))
+}
+
+/// Converts a winrate to a fraction, accepting Leela Zero's scale of 0 to 10000.
+fn normalize_winrate(winrate: f64) -> f64 {
+    if winrate > 1.0 {
+        winrate / 10000.0
+    } else {
+        winrate
+    }
+}
+
+fn grid_ownership(rows: &[Vec<f64>]) -> HashMap<(u8, u8), f64> {
+    let mut ownership = HashMap::new();
+    for (y, row) in rows.iter().enumerate() {
+        for (x, &value) in row.iter().enumerate() {
+            // Points past the largest board can't be drawn anyway.
+            if let (Ok(x), Ok(y)) = (u8::try_from(x), u8::try_from(y)) {
+                ownership.insert((x, y), value);
+            }
+        }
+    }
+    ownership
+}
+
+fn clamped(ownership: HashMap<(u8, u8), f64>) -> HashMap<(u8, u8), f64> {
+    ownership
+        .into_iter()
+        .filter(|(_, value)| value.is_finite())
+        .map(|(point, value)| (point, value.clamp(-1.0, 1.0)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::Transform;
+
+    use super::{parse_gtp_point, Analysis};
+
+    #[test]
+    fn json_points() {
+        let data = r#"{
+            "ownership": {"aa": 0.5, "ba": -2.0},
+            "candidates": [{"point": "cc", "winrate": 0.55, "score_lead": 1.5}]
+        }"#;
+        let analysis = Analysis::from_json(data).unwrap();
+        assert_eq!(analysis.ownership[&(0, 0)], 0.5);
+        assert_eq!(analysis.ownership[&(1, 0)], -1.0);
+        assert_eq!(analysis.candidates.len(), 1);
+        assert_eq!(analysis.candidates[0].point, (2, 2));
+        assert_eq!(analysis.candidates[0].winrate, Some(0.55));
+        assert_eq!(analysis.candidates[0].visits, None);
+    }
+
+    #[test]
+    fn json_arrays() {
+        let rows = Analysis::from_json(r#"{"ownership": [[0.1, 0.2], [0.3, 0.4]]}"#).unwrap();
+        let flat = Analysis::from_json(r#"{"ownership": [0.1, 0.2, 0.3, 0.4]}"#).unwrap();
+        assert_eq!(rows, flat);
+        assert_eq!(rows.ownership[&(0, 1)], 0.3);
+        assert!(Analysis::from_json(r#"{"ownership": [0.1, 0.2, 0.3]}"#).is_err());
+    }
+
+    #[test]
+    fn points_off_the_board() {
+        let data = r#"{
+            "ownership": {"zz": 0.5, "ee": -0.5},
+            "candidates": [{"point": "jj"}, {"point": "cc"}]
+        }"#;
+        let analysis = Analysis::from_json(data).unwrap();
+        let transform = Transform {
+            flip_horizontal: true,
+            ..Default::default()
+        };
+        let flipped = analysis.transformed(&transform, (9, 9));
+        assert_eq!(flipped.ownership.len(), 1);
+        assert_eq!(flipped.ownership[&(4, 4)], -0.5);
+        assert_eq!(flipped.candidates.len(), 1);
+        assert_eq!(flipped.candidates[0].point, (6, 2));
+
+        let rows = vec![vec![0.5; 19]; 19];
+        let grid = Analysis::from_json(&serde_json::json!({ "ownership": rows }).to_string())
+            .unwrap()
+            .transformed(&transform, (9, 9));
+        assert_eq!(grid.ownership.len(), 81);
+    }
+
+    #[test]
+    fn csv() {
+        let analysis = Analysis::from_csv("point,value\n# comment\ndd, 0.75\n\nee,-1\n").unwrap();
+        assert_eq!(analysis.ownership.len(), 2);
+        assert_eq!(analysis.ownership[&(3, 3)], 0.75);
+        assert_eq!(analysis.ownership[&(4, 4)], -1.0);
+        assert!(Analysis::from_csv("dd").is_err());
+    }
+
+    #[test]
+    fn gtp_points() {
+        assert_eq!(parse_gtp_point("A19", (19, 19)), Some((0, 0)));
+        assert_eq!(parse_gtp_point("q16", (19, 19)), Some((15, 3)));
+        assert_eq!(parse_gtp_point("J1", (9, 9)), Some((8, 8)));
+        assert_eq!(parse_gtp_point("I5", (19, 19)), None);
+        assert_eq!(parse_gtp_point("K1", (9, 9)), None);
+        assert_eq!(parse_gtp_point("pass", (19, 19)), None);
+    }
+}
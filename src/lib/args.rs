@@ -2,12 +2,22 @@ use std::path::PathBuf;
 
 use clap::builder::styling::{AnsiColor, Styles};
 use clap::Parser;
+use regex::Regex;
 
+use crate::analysis::Analysis;
+use crate::book::{BookOptions, SolutionPlacement};
 use crate::errors::UsageError;
+use crate::goban::GobanOptions;
+use crate::node_selector::{NodeSelector, TreePath};
+use crate::render::animation::AnimationOptions;
+use crate::render::grid::GridOptions;
+use crate::render::overlay::VariationOverlay;
 use crate::render::{
     BoardSideSet, GeneratedStyle, GobanRange, MoveNumberOptions, NodeDescription, RenderOptions,
+    Transform, VariationMarkup,
 };
-use crate::text::TileSet;
+use crate::scoring::ScoringRules;
+use crate::text::{MarkupTileSet, TileSet};
 
 // clap v3 styling
 const CLAP_STYLES: Styles = Styles::styled()
@@ -25,7 +35,7 @@ pub struct SgfRenderArgs {
     #[arg(value_name = "FILE", global = true)]
     pub infile: Option<PathBuf>,
     /// Output file [default: write to stdout].
-    #[arg(short, long, value_name = "FILE")]
+    #[arg(short, long, value_name = "FILE", conflicts_with = "kifu_pages")]
     pub outfile: Option<PathBuf>,
     /// Output format.
     #[arg(short = 'f', long = "format", default_value = "svg")]
@@ -34,20 +44,202 @@ pub struct SgfRenderArgs {
     /// Parse the SGF file even if it contains errors (may drop invalid data)
     #[arg(short, long, default_value_t = false)]
     pub lenient: bool,
+    /// Check for illegal moves (ko, superko, suicide, or playing on an occupied point).
+    #[arg(long, value_name = "MODE", default_value = "off")]
+    pub check_rules: RuleCheckMode,
+    /// Nodes to render in batch mode as [GAME:]VARIATION:NODE (e.g. '0:10,0:20,3:last').
+    #[arg(
+        long,
+        value_name = "NODES",
+        value_delimiter = ',',
+        group = "batch",
+        conflicts_with_all = [
+            "nodes_file",
+            "overlay_variation",
+            "node_selector",
+            "game_number",
+            "variation",
+            "node_number",
+            "kifu_pages",
+        ]
+    )]
+    pub nodes: Vec<NodeDescription>,
+    /// File listing nodes to render in batch mode, one per line.
+    #[arg(
+        long,
+        value_name = "FILE",
+        group = "batch",
+        conflicts_with_all = [
+            "game_number",
+            "variation",
+            "node_number",
+            "kifu_pages",
+            "overlay_variation",
+            "node_selector",
+        ]
+    )]
+    pub nodes_file: Option<PathBuf>,
+    /// Output filename template for batch mode and kifu pages
+    /// [default: '{game}-{variation}-{node}.{ext}', or '{game}-{variation}-{page}.{ext}' with
+    /// '--kifu-pages'].
+    #[arg(long, value_name = "TEMPLATE")]
+    pub outfile_template: Option<String>,
+    /// Lay out the nodes from batch mode as a grid in a single image.
+    #[arg(long, requires = "batch", conflicts_with = "outfile_template")]
+    pub grid: bool,
+    /// Number of diagrams in each row of the grid.
+    #[arg(
+        long,
+        value_name = "NUM",
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "grid"
+    )]
+    grid_columns: u64,
+    /// Space between diagrams in the grid in pixels.
+    #[arg(long, value_name = "GAP", default_value_t = 40.0, requires = "grid")]
+    grid_gap: f64,
+    /// Title to draw above the grid.
+    #[arg(long, value_name = "TITLE", requires = "grid")]
+    grid_title: Option<String>,
+    /// Delay between animation frames in milliseconds.
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 1000,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    frame_delay: u16,
+    /// Range of moves to animate (e.g. '50-100') [default: all moves].
+    #[arg(long, value_name = "RANGE")]
+    animate_moves: Option<MoveNumberRange>,
     #[clap(flatten)]
     pub render_args: RenderArgs,
 }
 
+impl SgfRenderArgs {
+    /// Returns the nodes to render in batch mode (empty if not in batch mode).
+    pub fn batch_nodes(&self) -> Result<Vec<NodeDescription>, UsageError> {
+        // A grid is a single image, but otherwise each node gets its own file.
+        if self.outfile.is_some()
+            && !self.grid
+            && (!self.nodes.is_empty() || self.nodes_file.is_some())
+        {
+            return Err(UsageError::OutfileInBatchMode);
+        }
+        match &self.nodes_file {
+            Some(filename) => {
+                let data = std::fs::read_to_string(filename)
+                    .map_err(|e| UsageError::NodesFileReadError(e.into()))?;
+                data.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::parse)
+                    .collect()
+            }
+            None => Ok(self.nodes.clone()),
+        }
+    }
+
+    /// Returns the options for animated output formats.
+    pub fn animation_options(&self) -> AnimationOptions {
+        let range = self.animate_moves.unwrap_or(MoveNumberRange {
+            start: 1,
+            end: None,
+        });
+        AnimationOptions {
+            frame_delay: self.frame_delay,
+            first_move: range.start,
+            last_move: range.end,
+        }
+    }
+
+    /// Returns the options for laying out batch nodes as a grid.
+    pub fn grid_options(&self) -> GridOptions {
+        GridOptions {
+            columns: self.grid_columns as usize,
+            gap: self.grid_gap,
+            title: self.grid_title.clone(),
+        }
+    }
+
+    /// Returns the options for what to track while building gobans.
+    pub fn goban_options(&self) -> GobanOptions {
+        GobanOptions {
+            check_rules: self.check_rules != RuleCheckMode::Off,
+            sgf_analysis: self.render_args.sgf_analysis,
+        }
+    }
+
+    /// Returns the output path for a node rendered in batch mode, or for a single kifu page.
+    pub fn batch_outfile(
+        &self,
+        node_description: &NodeDescription,
+        page: Option<usize>,
+    ) -> PathBuf {
+        let default_template = match page {
+            Some(_) => "{game}-{variation}-{page}.{ext}",
+            None => "{game}-{variation}-{node}.{ext}",
+        };
+        self.outfile_template
+            .as_deref()
+            .unwrap_or(default_template)
+            .replace("{page}", &page.unwrap_or(0).to_string())
+            .replace("{game}", &node_description.game_number.to_string())
+            .replace("{variation}", &node_description.variation.to_string())
+            .replace("{node}", &node_description.node_number.to_string())
+            .replace("{ext}", self.output_format.extension())
+            .into()
+    }
+}
+
 #[derive(Debug, clap::Subcommand)]
 pub enum Command {
     /// Print a tree of the SGF's variations
     Query(QueryArgs),
+    /// Lay out problems from many SGF files as a PDF or directory of SVG pages
+    Book(Box<BookArgs>),
 }
 
 #[derive(Debug, Parser)]
 pub struct RenderArgs {
     #[clap(flatten)]
     node_description: NodeDescription,
+    /// Select the node with this name (N property) instead of by number.
+    #[arg(
+        long,
+        value_name = "NAME",
+        group = "node_selector",
+        conflicts_with_all = ["variation", "node_number"]
+    )]
+    node_name: Option<String>,
+    /// Select the node where move NUM is played in the selected variation.
+    #[arg(
+        long = "move",
+        value_name = "NUM",
+        group = "node_selector",
+        conflicts_with = "node_number"
+    )]
+    move_number: Option<u64>,
+    /// Select the node whose comment matches REGEX.
+    #[arg(
+        long,
+        value_name = "REGEX",
+        value_parser = Regex::new,
+        group = "node_selector",
+        conflicts_with_all = ["variation", "node_number"]
+    )]
+    comment_matches: Option<Regex>,
+    /// Select the variation by the branch to take at each fork in turn (e.g. '0.2.1').
+    #[arg(
+        long,
+        value_name = "PATH",
+        group = "node_selector",
+        conflicts_with = "variation"
+    )]
+    tree_path: Option<TreePath>,
+    #[clap(flatten)]
+    transform: Transform,
     /// Width of the output image in pixels.
     #[arg(
         short = 'w',
@@ -68,6 +260,12 @@ pub struct RenderArgs {
     /// Custom style `toml` file. Conflicts with '--style'. See the README for details.
     #[arg(long, value_name = "FILE", conflicts_with = "generated_style")]
     custom_style: Option<PathBuf>,
+    /// JSON or CSV file with ownership and candidate moves to draw. See the README for details.
+    #[arg(long, value_name = "FILE")]
+    analysis: Option<PathBuf>,
+    /// Draw engine analysis stored in the SGF at the selected node. See the README for details.
+    #[arg(long, conflicts_with = "analysis")]
+    sgf_analysis: bool,
     /// Draw move numbers (may replace other markup).
     #[arg(long, require_equals=true, num_args = 0..=1, value_name = "RANGE", default_missing_value = "1")]
     move_numbers: Option<MoveNumberRange>,
@@ -88,6 +286,10 @@ pub struct RenderArgs {
     /// Tileset to use for text rendering (11 characters)
     #[arg(long, default_value = "●○┏┓┗┛┯┠┷┨┼")]
     tileset: TileSet,
+    /// Characters for text marks, triangles, circles and squares on empty points, then on white
+    /// stones, then on black stones.
+    #[arg(long, value_name = "CHARS", default_value = "✕△◯□✖▲◉■⊗◬◎▣")]
+    markup_tileset: MarkupTileSet,
     /// Don't draw SGF marks.
     #[clap(long = "no-marks", action = clap::ArgAction::SetFalse)]
     draw_marks: bool,
@@ -115,15 +317,101 @@ pub struct RenderArgs {
     /// Don't draw SGF arrows.
     #[clap(long = "no-arrows", action = clap::ArgAction::SetFalse)]
     draw_arrows: bool,
+    /// Don't draw SGF territory.
+    #[clap(long = "no-territory", action = clap::ArgAction::SetFalse)]
+    draw_territory: bool,
+    /// Dim dead stones (stones inside the opponent's SGF territory).
+    #[clap(long)]
+    dim_dead_stones: bool,
+    /// Draw a caption with the number of prisoners taken by each player.
+    #[clap(long = "captures")]
+    draw_captures: bool,
+    /// Draw a caption with the score estimated from the position (taking stones in SGF territory
+    /// as dead), komi and the game result.
+    #[clap(long = "score")]
+    draw_score: bool,
+    /// Rules to score with: japanese (territory) or chinese (area). Requires --score.
+    #[arg(
+        long,
+        value_name = "RULES",
+        default_value = "japanese",
+        requires = "draw_score"
+    )]
+    rules: ScoringRules,
+    /// Draw the node's comment below the board.
+    #[clap(long = "comment")]
+    draw_comment: bool,
+    /// Draw the players, event, date, komi and result above the board.
+    #[clap(long = "game-info")]
+    draw_game_info: bool,
+    /// Draw move annotation glyphs (e.g. '!' for TE, '?' for BM) next to the numbered moves, or
+    /// the last move.
+    #[clap(long)]
+    annotations: bool,
+    /// List the annotations of numbered moves below the diagram.
+    #[clap(long)]
+    annotation_footnotes: bool,
+    /// Mark the last move, or the last N moves with fading intensity.
+    #[arg(
+        long,
+        require_equals = true,
+        num_args = 0..=1,
+        value_name = "N",
+        default_missing_value = "1",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["kifu", "kifu_pages", "overlay_variation"]
+    )]
+    last_move: Option<u64>,
+    /// Draw the moves of the node's variations as letters (with the main line highlighted) or as
+    /// translucent stones (with the main line circled).
+    #[arg(long, value_name = "MODE")]
+    variation_markup: Option<VariationMarkup>,
+    /// Number the moves of VARIATION after the selected node from 1, drawn over the node's
+    /// position.
+    #[arg(
+        long,
+        value_name = "VARIATION",
+        conflicts_with_all = ["move_numbers", "kifu", "kifu_pages"]
+    )]
+    overlay_variation: Option<u64>,
+    /// Number of variation moves to overlay [default: all].
+    #[arg(
+        long,
+        value_name = "NUM",
+        value_parser = clap::value_parser!(u64).range(1..),
+        requires = "overlay_variation"
+    )]
+    overlay_moves: Option<u64>,
     /// Don't draw any markup on points.
     #[clap(long)]
     no_point_markup: bool,
     /// Generate a kifu.
     #[clap(long)]
     kifu: bool,
+    /// Split the game into a series of kifu diagrams with NUM moves each.
+    #[arg(
+        long,
+        value_name = "NUM",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "move_numbers"
+    )]
+    kifu_pages: Option<u64>,
 }
 
 impl RenderArgs {
+    /// Returns the selector to find the node with, if not selecting it by number.
+    pub fn node_selector(&self) -> Option<NodeSelector> {
+        if let Some(name) = &self.node_name {
+            Some(NodeSelector::Name(name.clone()))
+        } else if let Some(move_number) = self.move_number {
+            Some(NodeSelector::Move(move_number))
+        } else if let Some(regex) = &self.comment_matches {
+            Some(NodeSelector::CommentMatches(regex.clone()))
+        } else {
+            self.tree_path.clone().map(NodeSelector::TreePath)
+        }
+    }
+
     /// Map RenderArgs to RenderOptions.
     pub fn options(&self, output_format: &OutputFormat) -> Result<RenderOptions, UsageError> {
         let goban_range = if self.shrink_wrap {
@@ -143,6 +431,34 @@ impl RenderArgs {
             None => self.generated_style.style().clone(),
         };
 
+        // Only the SVG based formats can draw engine analysis.
+        let non_svg_format = match output_format {
+            OutputFormat::Text => Some("text"),
+            OutputFormat::Sensei => Some("Sensei's Library"),
+            OutputFormat::Latex => Some("LaTeX"),
+            _ => None,
+        };
+        if let Some(format) = non_svg_format {
+            if self.analysis.is_some() || self.sgf_analysis {
+                return Err(UsageError::InvalidOutputOption(
+                    "Engine analysis".to_owned(),
+                    format,
+                ));
+            }
+        }
+        let analysis = match &self.analysis {
+            Some(filename) => {
+                let data = std::fs::read_to_string(filename)
+                    .map_err(|e| UsageError::AnalysisReadError(e.into()))?;
+                if filename.extension().is_some_and(|ext| ext == "csv") {
+                    Some(Analysis::from_csv(&data)?)
+                } else {
+                    Some(Analysis::from_json(&data)?)
+                }
+            }
+            None => None,
+        };
+
         let count_from = self.move_numbers_from;
         let move_number_options = if let Some(range) = self.move_numbers {
             Some(MoveNumberOptions {
@@ -150,7 +466,7 @@ impl RenderArgs {
                 end: range.end,
                 count_from,
             })
-        } else if self.kifu {
+        } else if self.kifu || self.kifu_pages.is_some() {
             Some(MoveNumberOptions {
                 start: 1,
                 end: None,
@@ -167,16 +483,7 @@ impl RenderArgs {
             self.label_sides
         };
 
-        if output_format == &OutputFormat::Text {
-            if self.kifu {
-                return Err(UsageError::InvalidTextOutputOption("Kifu mode".to_owned()));
-            }
-            if move_number_options.is_some() {
-                return Err(UsageError::InvalidTextOutputOption(
-                    "Move numbers".to_owned(),
-                ));
-            }
-        }
+        let kifu_mode = self.kifu || self.kifu_pages.is_some();
 
         Ok(RenderOptions {
             node_description: self.node_description,
@@ -194,8 +501,28 @@ impl RenderArgs {
             draw_labels: self.draw_labels && !no_point_markup,
             draw_lines: self.draw_lines && !no_point_markup,
             draw_arrows: self.draw_arrows && !no_point_markup,
-            kifu_mode: self.kifu,
+            draw_territory: self.draw_territory && !no_point_markup,
+            dim_dead_stones: self.dim_dead_stones,
+            draw_captures: self.draw_captures,
+            draw_score: self.draw_score,
+            scoring_rules: self.rules,
+            last_moves: self.last_move,
+            draw_comment: self.draw_comment,
+            draw_game_info: self.draw_game_info,
+            transform: self.transform,
+            kifu_mode,
+            kifu_pages: self.kifu_pages,
+            variation_markup: self.variation_markup,
+            analysis,
+            draw_sgf_analysis: self.sgf_analysis,
+            draw_annotations: self.annotations,
+            annotation_footnotes: self.annotation_footnotes,
+            variation_overlay: self.overlay_variation.map(|variation| VariationOverlay {
+                variation,
+                moves: self.overlay_moves,
+            }),
             tileset: self.tileset.clone(),
+            markup_tileset: self.markup_tileset.clone(),
         })
     }
 }
@@ -204,8 +531,57 @@ impl RenderArgs {
 pub enum OutputFormat {
     Svg,
     Text,
+    Sensei,
+    Latex,
+    AnimatedSvg,
+    Html,
     #[cfg(feature = "png")]
     Png,
+    #[cfg(feature = "png")]
+    Apng,
+    #[cfg(feature = "png")]
+    Gif,
+    #[cfg(feature = "pdf")]
+    Pdf,
+}
+
+impl OutputFormat {
+    /// Whether the format shows the nodes of a variation in sequence.
+    pub fn is_animation(&self) -> bool {
+        match self {
+            OutputFormat::AnimatedSvg | OutputFormat::Html => true,
+            #[cfg(feature = "png")]
+            OutputFormat::Apng | OutputFormat::Gif => true,
+            _ => false,
+        }
+    }
+
+    /// Conventional file extension for the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Svg => "svg",
+            OutputFormat::Text => "txt",
+            OutputFormat::Sensei => "txt",
+            OutputFormat::Latex => "tex",
+            OutputFormat::AnimatedSvg => "svg",
+            OutputFormat::Html => "html",
+            #[cfg(feature = "png")]
+            OutputFormat::Png => "png",
+            #[cfg(feature = "png")]
+            OutputFormat::Apng => "png",
+            #[cfg(feature = "png")]
+            OutputFormat::Gif => "gif",
+            #[cfg(feature = "pdf")]
+            OutputFormat::Pdf => "pdf",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum, Eq, PartialEq)]
+pub enum RuleCheckMode {
+    Off,
+    Warn,
+    Error,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -242,6 +618,37 @@ pub struct QueryArgs {
     /// Print the index of the last node in the selected variation.
     #[clap(long, group = "mode")]
     pub last_node: bool,
+    /// Score the final position of the selected variation.
+    #[clap(long, group = "mode")]
+    pub score: bool,
+    /// Print each game's players, result, komi, handicap, rules, date, event, board size and
+    /// number of moves.
+    #[clap(long, group = "mode")]
+    pub info: bool,
+    /// Rules to score with: japanese (territory) or chinese (area). Requires --score.
+    #[arg(
+        long,
+        value_name = "RULES",
+        default_value = "japanese",
+        requires = "score"
+    )]
+    pub rules: ScoringRules,
+    /// Comma separated dead stones (e.g. 'cc,dd'). Defaults to stones in TB/TW territory.
+    #[arg(
+        long,
+        value_name = "POINTS",
+        value_delimiter = ',',
+        value_parser = parse_point,
+        requires = "score"
+    )]
+    pub dead_stones: Option<Vec<(u8, u8)>>,
+    /// Print the game tree (or --info) as JSON. The tree lists each variation's node range, first
+    /// move, node names and commented nodes.
+    #[clap(
+        long,
+        conflicts_with_all = ["score", "last_game", "last_variation", "last_node"]
+    )]
+    pub json: bool,
     /// Game number to query.
     #[arg(short, long, default_value_t = 0)]
     pub game_number: u64,
@@ -250,12 +657,90 @@ pub struct QueryArgs {
     pub variation: u64,
 }
 
+#[derive(Debug, Parser)]
+#[command(
+    mut_arg("game_number", |arg| arg.hide(true)),
+    mut_arg("variation", |arg| arg.hide(true)),
+    mut_arg("node_number", |arg| arg.hide(true)),
+    mut_arg("node_name", |arg| arg.hide(true)),
+    mut_arg("move_number", |arg| arg.hide(true)),
+    mut_arg("comment_matches", |arg| arg.hide(true)),
+    mut_arg("tree_path", |arg| arg.hide(true)),
+    mut_arg("kifu_pages", |arg| arg.hide(true)),
+    mut_arg("overlay_variation", |arg| arg.hide(true)),
+    mut_arg("overlay_moves", |arg| arg.hide(true)),
+)]
+pub struct BookArgs {
+    /// More SGF files to read problems from, after FILE. Each game in a file is one problem.
+    #[arg(value_name = "FILES", index = 2)]
+    pub more_files: Vec<PathBuf>,
+    /// Output PDF file, or directory to write page SVGs to if the name doesn't end in '.pdf'.
+    #[arg(short, long, value_name = "PATH")]
+    pub outfile: PathBuf,
+    /// Parse the SGF files even if they contain errors (may drop invalid data)
+    #[arg(short, long, default_value_t = false)]
+    pub lenient: bool,
+    /// Title to print at the top of each page.
+    #[arg(long)]
+    title: Option<String>,
+    /// Number of problems on each page.
+    #[arg(
+        long,
+        value_name = "NUM",
+        default_value_t = 6,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    per_page: u64,
+    /// Number of columns of problems on each page.
+    #[arg(
+        long,
+        value_name = "NUM",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    columns: u64,
+    /// Where to print solutions: not at all, on the page after each page of problems, or in an
+    /// answer section at the end.
+    #[arg(long, value_name = "MODE", default_value = "none")]
+    solutions: SolutionPlacement,
+    /// Variation holding each problem's solution.
+    #[arg(long, value_name = "NUM", default_value_t = 0)]
+    solution_variation: u64,
+    #[clap(flatten)]
+    render_args: RenderArgs,
+}
+
+impl BookArgs {
+    /// Map BookArgs to BookOptions.
+    pub fn options(&self) -> Result<BookOptions, UsageError> {
+        if self.render_args.kifu_pages.is_some() {
+            return Err(UsageError::KifuPagesInBook);
+        }
+        if self.render_args.overlay_variation.is_some() {
+            return Err(UsageError::OverlayInBook);
+        }
+        if self.render_args.node_selector().is_some() {
+            return Err(UsageError::NodeSelectorInBook);
+        }
+        Ok(BookOptions {
+            title: self.title.clone(),
+            per_page: self.per_page as usize,
+            columns: self.columns as usize,
+            solutions: self.solutions,
+            solution_variation: self.solution_variation,
+            render_options: self.render_args.options(&OutputFormat::Svg)?,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum QueryMode {
     Default,
     LastGame,
     LastVariation,
     LastNode,
+    Score,
+    Info,
 }
 
 impl QueryArgs {
@@ -266,8 +751,24 @@ impl QueryArgs {
             QueryMode::LastVariation
         } else if self.last_node {
             QueryMode::LastNode
+        } else if self.score {
+            QueryMode::Score
+        } else if self.info {
+            QueryMode::Info
         } else {
             QueryMode::Default
         }
     }
 }
+
+pub(crate) fn parse_point(s: &str) -> Result<(u8, u8), UsageError> {
+    let parse_byte = |b: u8| match b {
+        b'a'..=b'z' => Ok(b - b'a'),
+        b'A'..=b'Z' => Ok(b - b'A' + 26),
+        _ => Err(UsageError::InvalidPoint(s.to_string())),
+    };
+    match s.as_bytes() {
+        &[x, y] => Ok((parse_byte(x)?, parse_byte(y)?)),
+        _ => Err(UsageError::InvalidPoint(s.to_string())),
+    }
+}
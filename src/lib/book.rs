@@ -0,0 +1,294 @@
+use minidom::Element;
+
+use crate::errors::GobanError;
+use crate::goban::{parse_collection, Goban, GobanOptions};
+use crate::render::svg::{self, prefix_ids, NAMESPACE};
+use crate::render::{GobanRange, MoveNumberOptions, NodeDescription, NodeNumber, RenderOptions};
+
+// A4 in points.
+static PAGE_WIDTH: f64 = 595.0;
+static PAGE_HEIGHT: f64 = 842.0;
+static PAGE_MARGIN: f64 = 42.0;
+static HEADER_HEIGHT: f64 = 28.0;
+static FOOTER_HEIGHT: f64 = 20.0;
+static CELL_PADDING: f64 = 8.0;
+static NUMBER_HEIGHT: f64 = 18.0;
+static FONT_SIZE: f64 = 12.0;
+static FONT_FAMILY: &str = "Inter";
+static FONT_WEIGHT: usize = 700;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SolutionPlacement {
+    None,
+    NextPage,
+    Answers,
+}
+
+#[derive(Debug, Clone)]
+pub struct BookOptions {
+    pub title: Option<String>,
+    pub per_page: usize,
+    pub columns: usize,
+    pub solutions: SolutionPlacement,
+    pub solution_variation: u64,
+    pub render_options: RenderOptions,
+}
+
+/// A problem's starting position, and the final position of its solution if it has one.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub position: Goban,
+    pub solution: Option<Goban>,
+}
+
+/// Reads a problem from each game in an SGF collection.
+///
+/// The problem is the game's root node, and the solution is the last node of the solution
+/// variation.
+pub fn read_problems(
+    sgf: &str,
+    strict: bool,
+    options: &BookOptions,
+) -> Result<Vec<Problem>, GobanError> {
+    let collection = parse_collection(sgf, strict)?;
+    let goban_options = GobanOptions {
+        sgf_analysis: options.render_options.draw_sgf_analysis,
+        ..Default::default()
+    };
+    let mut problems = vec![];
+    for game_number in 0..collection.len() as u64 {
+        let position_description = NodeDescription {
+            game_number,
+            variation: 0,
+            node_number: NodeNumber::Number(0),
+        };
+        let solution_description = NodeDescription {
+            game_number,
+            variation: options.solution_variation,
+            node_number: NodeNumber::Last,
+        };
+        let position =
+            Goban::from_collection(&collection, &[position_description], &goban_options)?
+                .pop()
+                .unwrap();
+        let solution =
+            match Goban::from_collection(&collection, &[solution_description], &goban_options) {
+                Ok(mut gobans) => gobans.pop().filter(|goban| goban.moves().next().is_some()),
+                Err(GobanError::MissingVariation) => None,
+                Err(e) => return Err(e),
+            };
+        problems.push(Problem { position, solution });
+    }
+    Ok(problems)
+}
+
+/// Lays out the problems, and their solutions if requested, as a series of page SVGs.
+pub fn render_book(
+    problems: &[Problem],
+    options: &BookOptions,
+) -> Result<Vec<Element>, GobanError> {
+    let problem_options = RenderOptions {
+        draw_comment: true,
+        ..options.render_options.clone()
+    };
+    let solution_options = RenderOptions {
+        draw_comment: true,
+        move_number_options: options.render_options.move_number_options.or(Some(
+            MoveNumberOptions {
+                start: 1,
+                end: None,
+                count_from: 1,
+            },
+        )),
+        ..options.render_options.clone()
+    };
+
+    // Pages are stored as whether they hold solutions, and their numbered diagrams.
+    let mut problem_pages = vec![];
+    let mut solution_pages = vec![];
+    for (page_number, chunk) in problems.chunks(options.per_page).enumerate() {
+        let first = page_number * options.per_page + 1;
+        let mut diagrams = vec![];
+        let mut solutions = vec![];
+        for (i, problem) in chunk.iter().enumerate() {
+            // Shrink wrap to the solution so the problem and solution show the same area.
+            let ranges = match (&problem.solution, &options.render_options.goban_range) {
+                (Some(solution), GobanRange::ShrinkWrap) => {
+                    let transformed = solution.transformed(&options.render_options.transform);
+                    let (x_range, y_range) =
+                        GobanRange::ShrinkWrap.get_ranges(&transformed, &solution_options)?;
+                    Some(GobanRange::Ranged(x_range, y_range))
+                }
+                _ => None,
+            };
+            let with_ranges = |options: &RenderOptions| match &ranges {
+                Some(goban_range) => RenderOptions {
+                    goban_range: goban_range.clone(),
+                    ..options.clone()
+                },
+                None => options.clone(),
+            };
+            diagrams.push((
+                first + i,
+                Some(svg::render(
+                    &problem.position,
+                    &with_ranges(&problem_options),
+                )?),
+            ));
+            if options.solutions != SolutionPlacement::None {
+                let solution = match &problem.solution {
+                    Some(solution) => Some(svg::render(solution, &with_ranges(&solution_options))?),
+                    None => None,
+                };
+                solutions.push((first + i, solution));
+            }
+        }
+        problem_pages.push((false, diagrams));
+        if options.solutions != SolutionPlacement::None {
+            solution_pages.push((true, solutions));
+        }
+    }
+    let pages = match options.solutions {
+        SolutionPlacement::None => problem_pages,
+        SolutionPlacement::NextPage => problem_pages
+            .into_iter()
+            .zip(solution_pages)
+            .flat_map(|(problems, solutions)| vec![problems, solutions])
+            .collect(),
+        SolutionPlacement::Answers => problem_pages.into_iter().chain(solution_pages).collect(),
+    };
+
+    Ok(pages
+        .into_iter()
+        .enumerate()
+        .map(|(i, (is_solution, diagrams))| build_page(i + 1, is_solution, diagrams, options))
+        .collect())
+}
+
+fn build_page(
+    page_number: usize,
+    is_solution: bool,
+    diagrams: Vec<(usize, Option<Element>)>,
+    options: &BookOptions,
+) -> Element {
+    let mut builder = Element::builder("svg", NAMESPACE)
+        .attr("viewBox", format!("0 0 {PAGE_WIDTH} {PAGE_HEIGHT}"))
+        .attr("width", PAGE_WIDTH.to_string())
+        .attr("height", PAGE_HEIGHT.to_string())
+        .attr("font-size", FONT_SIZE.to_string())
+        .attr("font-family", FONT_FAMILY)
+        .attr("font-weight", FONT_WEIGHT)
+        .append(
+            Element::builder("rect", NAMESPACE)
+                .attr("fill", "white")
+                .attr("height", "100%")
+                .attr("width", "100%")
+                .build(),
+        );
+
+    let header_y = PAGE_MARGIN + FONT_SIZE;
+    if let Some(title) = &options.title {
+        builder = builder.append(text(title, PAGE_MARGIN, header_y, "start"));
+    }
+    if is_solution {
+        builder = builder.append(text("Solutions", PAGE_WIDTH - PAGE_MARGIN, header_y, "end"));
+    }
+    builder = builder.append(text(
+        &page_number.to_string(),
+        PAGE_WIDTH / 2.0,
+        PAGE_HEIGHT - PAGE_MARGIN,
+        "middle",
+    ));
+
+    let rows = options.per_page.div_ceil(options.columns);
+    let cell_width = (PAGE_WIDTH - 2.0 * PAGE_MARGIN) / options.columns as f64;
+    let cell_height =
+        (PAGE_HEIGHT - 2.0 * PAGE_MARGIN - HEADER_HEIGHT - FOOTER_HEIGHT) / rows as f64;
+    for (i, (number, diagram)) in diagrams.into_iter().enumerate() {
+        let x = PAGE_MARGIN + (i % options.columns) as f64 * cell_width + CELL_PADDING;
+        let y = PAGE_MARGIN + HEADER_HEIGHT + (i / options.columns) as f64 * cell_height;
+        builder = builder.append(text(&format!("{number}."), x, y + FONT_SIZE, "start"));
+        if let Some(mut diagram) = diagram {
+            prefix_ids(&mut diagram, &format!("p{number}-"));
+            diagram.set_attr("x", x.to_string());
+            diagram.set_attr("y", (y + NUMBER_HEIGHT).to_string());
+            diagram.set_attr("width", (cell_width - 2.0 * CELL_PADDING).to_string());
+            diagram.set_attr(
+                "height",
+                (cell_height - NUMBER_HEIGHT - CELL_PADDING).to_string(),
+            );
+            diagram.set_attr("preserveAspectRatio", "xMidYMin meet");
+            builder = builder.append(diagram);
+        }
+    }
+    builder.build()
+}
+
+fn text(content: &str, x: f64, y: f64, anchor: &str) -> Element {
+    Element::builder("text", NAMESPACE)
+        .attr("x", x.to_string())
+        .attr("y", y.to_string())
+        .attr("text-anchor", anchor)
+        .append(content)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::RenderOptions;
+
+    use super::{read_problems, render_book, BookOptions, SolutionPlacement};
+
+    static TEST_DATA: &str = "(;SZ[9]AB[cc]AW[dc]C[Black to play];B[dd]C[Correct])\
+                              (;SZ[9]AB[ee]AW[fe];B[ff])\
+                              (;SZ[9]AB[aa]AW[ba])";
+
+    fn options(solutions: SolutionPlacement) -> BookOptions {
+        BookOptions {
+            title: None,
+            per_page: 2,
+            columns: 2,
+            solutions,
+            solution_variation: 0,
+            render_options: RenderOptions::default(),
+        }
+    }
+
+    #[test]
+    fn reads_problems() {
+        let problems = read_problems(TEST_DATA, true, &options(SolutionPlacement::None)).unwrap();
+        assert_eq!(problems.len(), 3);
+        assert_eq!(problems[0].position.moves().count(), 0);
+        assert_eq!(problems[0].position.comment(), Some("Black to play"));
+        assert_eq!(
+            problems[0].solution.as_ref().unwrap().comment(),
+            Some("Correct")
+        );
+        assert!(problems[2].solution.is_none());
+    }
+
+    #[test]
+    fn page_order() {
+        let solution_pages_for = |solutions| {
+            let options = options(solutions);
+            let problems = read_problems(TEST_DATA, true, &options).unwrap();
+            let pages = render_book(&problems, &options).unwrap();
+            pages
+                .iter()
+                .map(|page| page.children().any(|child| child.text() == "Solutions"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            solution_pages_for(SolutionPlacement::None),
+            vec![false, false]
+        );
+        assert_eq!(
+            solution_pages_for(SolutionPlacement::NextPage),
+            vec![false, true, false, true]
+        );
+        assert_eq!(
+            solution_pages_for(SolutionPlacement::Answers),
+            vec![false, false, true, true]
+        );
+    }
+}
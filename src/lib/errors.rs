@@ -1,5 +1,7 @@
 use sgf_parse::SgfParseError;
 
+use crate::render::NodeDescription;
+
 #[derive(Debug)]
 pub enum GobanError {
     ParseError(SgfParseError),
@@ -11,6 +13,12 @@ pub enum GobanError {
     InvalidRange,
     UnlabellableRange,
     InvalidSzProperty,
+    RuleViolation(RuleViolation),
+    UnrepresentableMoveNumbers(&'static str),
+    UnrepresentableLabel(String),
+    UnrelatedVariation,
+    NoMatchingNode,
+    AmbiguousNodeSelector(Vec<NodeDescription>),
 }
 
 impl std::fmt::Display for GobanError {
@@ -19,12 +27,40 @@ impl std::fmt::Display for GobanError {
             Self::ParseError(e) => write!(f, "{e}"),
             Self::StyleDefError(e) => write!(f, "Invalid defs in style: {e}"),
             Self::InvalidMove => write!(f, "Invalid move"),
+            Self::UnrepresentableMoveNumbers(reason) => write!(f, "{reason}"),
+            Self::UnrepresentableLabel(label) => {
+                write!(f, "Label '{label}' is too long for a text diagram")
+            }
             Self::InsufficientSgfNodes => write!(f, "Insufficient SGF nodes found"),
             Self::MissingGame => write!(f, "Selected game not found"),
             Self::MissingVariation => write!(f, "Selected variation not found"),
             Self::InvalidRange => write!(f, "Invalid range to render in goban"),
             Self::UnlabellableRange => write!(f, "Range too large for use with labels"),
             Self::InvalidSzProperty => write!(f, "SZ property invalid"),
+            Self::RuleViolation(violation) => write!(f, "{violation}"),
+            Self::UnrelatedVariation => {
+                write!(
+                    f,
+                    "Overlay variation doesn't pass through the selected node"
+                )
+            }
+            Self::NoMatchingNode => write!(f, "No node matches the selector"),
+            Self::AmbiguousNodeSelector(matches) => {
+                let matches: Vec<String> = matches
+                    .iter()
+                    .map(|node| {
+                        format!(
+                            "{}:{}:{}",
+                            node.game_number, node.variation, node.node_number
+                        )
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "Node selector matches more than one node ({})",
+                    matches.join(", ")
+                )
+            }
         }
     }
 }
@@ -37,6 +73,37 @@ impl From<SgfParseError> for GobanError {
     }
 }
 
+/// An illegal move found while replaying a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleViolation {
+    OccupiedPoint { move_number: u64, point: (u8, u8) },
+    Suicide { move_number: u64, point: (u8, u8) },
+    Ko { move_number: u64, point: (u8, u8) },
+    Superko { move_number: u64, point: (u8, u8) },
+}
+
+impl std::fmt::Display for RuleViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (move_number, point, reason) = match self {
+            Self::OccupiedPoint { move_number, point } => (move_number, point, "point occupied"),
+            Self::Suicide { move_number, point } => (move_number, point, "suicide"),
+            Self::Ko { move_number, point } => (move_number, point, "ko"),
+            Self::Superko { move_number, point } => (move_number, point, "superko"),
+        };
+        let x = sgf_coordinate(point.0);
+        let y = sgf_coordinate(point.1);
+        write!(f, "Illegal move {move_number} at {x}{y} ({reason})")
+    }
+}
+
+pub(crate) fn sgf_coordinate(n: u8) -> char {
+    if n < 26 {
+        (b'a' + n) as char
+    } else {
+        (b'A' + n - 26) as char
+    }
+}
+
 #[derive(Debug)]
 pub enum UsageError {
     InvalidRange,
@@ -45,8 +112,19 @@ pub enum UsageError {
     InvalidLastMoveNumber,
     InvalidBoardSides,
     InvalidNodeNumber(String),
+    InvalidNodeDescription(String),
+    InvalidPoint(String),
     InvalidTextOutputOption(String),
+    InvalidOutputOption(String, &'static str),
+    NodesFileReadError(Box<dyn std::error::Error>),
     InvalidTileSet,
+    InvalidMarkupTileSet,
+    KifuPagesInBook,
+    OutfileInBatchMode,
+    OverlayInBook,
+    NodeSelectorInBook,
+    InvalidTreePath(String),
+    AnalysisReadError(Box<dyn std::error::Error>),
 }
 
 impl std::fmt::Display for UsageError {
@@ -58,10 +136,28 @@ impl std::fmt::Display for UsageError {
             UsageError::InvalidLastMoveNumber => write!(f, "Invalid last move number"),
             UsageError::InvalidBoardSides => write!(f, "Invalid board sides"),
             UsageError::InvalidNodeNumber(s) => write!(f, "Invalid node number '{s}'"),
+            UsageError::InvalidNodeDescription(s) => write!(f, "Invalid node description '{s}'"),
+            UsageError::InvalidPoint(s) => write!(f, "Invalid point '{s}'"),
             UsageError::InvalidTextOutputOption(s) => {
                 write!(f, "{s} not supported for text output")
             }
+            UsageError::InvalidOutputOption(s, format) => {
+                write!(f, "{s} not supported for {format} output")
+            }
+            UsageError::NodesFileReadError(e) => write!(f, "Failed to read nodes file: {e}"),
             UsageError::InvalidTileSet => write!(f, "Must be 11 characters long"),
+            UsageError::InvalidMarkupTileSet => write!(f, "Must be 12 characters long"),
+            UsageError::KifuPagesInBook => write!(f, "Kifu pages can't be used in books"),
+            UsageError::AnalysisReadError(e) => write!(f, "Failed to read analysis file: {e}"),
+            UsageError::OverlayInBook => write!(f, "Variation overlays can't be used in books"),
+            UsageError::NodeSelectorInBook => write!(f, "Node selectors can't be used in books"),
+            UsageError::InvalidTreePath(s) => write!(f, "Invalid tree path '{s}'"),
+            UsageError::OutfileInBatchMode => {
+                write!(
+                    f,
+                    "Batch mode uses '--outfile-template' rather than '--outfile'"
+                )
+            }
         }
     }
 }
@@ -76,6 +172,7 @@ pub enum QueryError {
     IoError(std::io::Error),
     GameNotFound,
     VariationNotFound,
+    GobanError(GobanError),
 }
 
 impl std::fmt::Display for QueryError {
@@ -85,6 +182,7 @@ impl std::fmt::Display for QueryError {
             QueryError::IoError(e) => write!(f, "{e}"),
             QueryError::GameNotFound => write!(f, "Game not found."),
             QueryError::VariationNotFound => write!(f, "Variation not found."),
+            QueryError::GobanError(e) => write!(f, "{e}"),
         }
     }
 }
@@ -97,6 +195,12 @@ impl From<SgfParseError> for QueryError {
     }
 }
 
+impl From<GobanError> for QueryError {
+    fn from(error: GobanError) -> Self {
+        Self::GobanError(error)
+    }
+}
+
 impl From<std::io::Error> for QueryError {
     fn from(error: std::io::Error) -> Self {
         Self::IoError(error)
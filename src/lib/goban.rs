@@ -1,11 +1,13 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
-use sgf_parse::{go, ParseOptions, SgfNode};
+use sgf_parse::{go, Double, GameTree, ParseOptions, SgfNode};
 
-use crate::errors::GobanError;
-use crate::render::{NodeDescription, NodeNumber};
+use crate::analysis::Analysis;
+use crate::errors::{GobanError, RuleViolation};
+use crate::render::{NodeDescription, NodeNumber, Transform};
 use crate::sgf_traversal::variation_nodes;
 
+#[derive(Debug, Clone)]
 pub struct Goban {
     size: (u8, u8),
     stones: HashMap<(u8, u8), StoneColor>,
@@ -21,6 +23,18 @@ pub struct Goban {
     arrows: HashSet<((u8, u8), (u8, u8))>,
     dimmed: HashSet<(u8, u8)>,
     labels: HashMap<(u8, u8), String>,
+    territory: HashMap<(u8, u8), StoneColor>,
+    komi: Option<f64>,
+    result: Option<String>,
+    comment: Option<String>,
+    game_info: GameInfo,
+    captures: Vec<(u64, StoneColor, u64)>,
+    goban_options: GobanOptions,
+    position_history: Vec<Vec<Stone>>,
+    rule_violations: Vec<RuleViolation>,
+    next_moves: Vec<(usize, Stone)>,
+    analysis: Option<Analysis>,
+    annotations: HashMap<u64, Vec<Annotation>>,
 }
 
 impl Goban {
@@ -44,34 +58,65 @@ impl Goban {
         node_description: &NodeDescription,
         strict: bool,
     ) -> Result<Self, GobanError> {
-        let parse_options = ParseOptions {
-            lenient: !strict,
-            ..Default::default()
-        };
-        let collection = sgf_parse::parse_with_options(sgf, &parse_options)?;
-        let root_node = collection
-            .get(node_description.game_number as usize)
-            .ok_or(GobanError::MissingGame)?
-            .as_go_node()?;
-        let board_size = get_board_size(root_node)?;
-        let mut goban = Goban::new(board_size);
-        let nodes = variation_nodes(root_node, node_description.variation)?;
-        let mut node_count = 0;
-        for node in nodes {
-            goban.process_node(node.sgf_node)?;
-            node_count += 1;
-            if let NodeNumber::Number(n) = node_description.node_number {
-                if node_count > n {
+        let collection = parse_collection(sgf, strict)?;
+        let mut gobans = Self::from_collection(
+            &collection,
+            std::slice::from_ref(node_description),
+            &GobanOptions::default(),
+        )?;
+        Ok(gobans.pop().unwrap())
+    }
+
+    /// Builds a goban for each node description from an already parsed collection.
+    ///
+    /// Descriptions which share a game and variation are all built from a single walk through
+    /// that variation's nodes.
+    pub fn from_collection(
+        collection: &[GameTree],
+        node_descriptions: &[NodeDescription],
+        goban_options: &GobanOptions,
+    ) -> Result<Vec<Self>, GobanError> {
+        let mut groups: BTreeMap<(u64, u64), Vec<usize>> = BTreeMap::new();
+        for (i, node_description) in node_descriptions.iter().enumerate() {
+            groups
+                .entry((node_description.game_number, node_description.variation))
+                .or_default()
+                .push(i);
+        }
+        let mut gobans: Vec<Option<Self>> = node_descriptions.iter().map(|_| None).collect();
+        for ((game_number, variation), indices) in groups {
+            let root_node = game_root(collection, game_number)?;
+            let board_size = get_board_size(root_node)?;
+            let mut goban = Goban::new(board_size, *goban_options);
+            let last_needed_node = indices
+                .iter()
+                .map(|&i| match node_descriptions[i].node_number {
+                    NodeNumber::Number(n) => Some(n),
+                    NodeNumber::Last => None,
+                })
+                .try_fold(0, |acc, n| n.map(|n| acc.max(n)));
+            for (node_number, node) in variation_nodes(root_node, variation)?.enumerate() {
+                let node_number = node_number as u64;
+                if last_needed_node.is_some_and(|n| node_number > n) {
                     break;
                 }
+                goban.process_node(node.sgf_node)?;
+                for &i in &indices {
+                    if node_descriptions[i].node_number == NodeNumber::Number(node_number) {
+                        gobans[i] = Some(goban.clone());
+                    }
+                }
             }
-        }
-        if let NodeNumber::Number(n) = node_description.node_number {
-            if n >= node_count {
-                return Err(GobanError::InsufficientSgfNodes);
+            for &i in &indices {
+                if node_descriptions[i].node_number == NodeNumber::Last {
+                    gobans[i] = Some(goban.clone());
+                }
             }
         }
-        Ok(goban)
+        gobans
+            .into_iter()
+            .map(|goban| goban.ok_or(GobanError::InsufficientSgfNodes))
+            .collect()
     }
 
     pub fn stones(&self) -> impl Iterator<Item = Stone> + '_ {
@@ -82,6 +127,9 @@ impl Goban {
         })
     }
 
+    /// Returns the stones on the board just before the given move was played.
+    ///
+    /// Falls back to the current position if no such move was played.
     pub fn stones_before_move(&self, move_number: u64) -> Box<dyn Iterator<Item = Stone> + '_> {
         let stones = self.stones_before_move.get(&move_number);
         match stones {
@@ -102,6 +150,120 @@ impl Goban {
         self.moves.iter().copied()
     }
 
+    pub fn territory(&self) -> impl Iterator<Item = ((u8, u8), StoneColor)> + '_ {
+        self.territory.iter().map(|(point, color)| (*point, *color))
+    }
+
+    /// Returns the stones inside the opponent's territory.
+    pub fn dead_stones(&self) -> impl Iterator<Item = Stone> + '_ {
+        self.stones().filter(move |stone| {
+            self.territory
+                .get(&(stone.x, stone.y))
+                .is_some_and(|&color| color != stone.color)
+        })
+    }
+
+    pub fn komi(&self) -> Option<f64> {
+        self.komi
+    }
+
+    pub fn result(&self) -> Option<&str> {
+        self.result.as_deref()
+    }
+
+    /// Returns the comment (`C`) on the current node.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    pub fn game_info(&self) -> &GameInfo {
+        &self.game_info
+    }
+
+    /// Returns `(move_number, color, count)` for each move which captured stones, where `color`
+    /// is the player credited with the prisoners.
+    pub fn captures(&self) -> impl Iterator<Item = (u64, StoneColor, u64)> + '_ {
+        self.captures.iter().copied()
+    }
+
+    /// Returns the total number of prisoners taken by the given player.
+    pub fn prisoners(&self, color: StoneColor) -> u64 {
+        self.captures()
+            .filter(|&(_, c, _)| c == color)
+            .map(|(_, _, count)| count)
+            .sum()
+    }
+
+    /// Returns any illegal moves (ko, superko, suicide, or occupied point) played so far.
+    ///
+    /// Ko and superko are only detected if the goban was built with `check_rules` set.
+    pub fn rule_violations(&self) -> impl Iterator<Item = RuleViolation> + '_ {
+        self.rule_violations.iter().copied()
+    }
+
+    /// Returns an error for the first illegal move played so far, if any.
+    pub fn check_rules(&self) -> Result<(), GobanError> {
+        match self.rule_violations.first() {
+            Some(violation) => Err(GobanError::RuleViolation(*violation)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a copy of the goban with the transform applied to every point.
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        let size = self.size;
+        let point = |p: (u8, u8)| transform.point(p, size);
+        let stone = |s: Stone| {
+            let (x, y) = point((s.x, s.y));
+            Stone { x, y, ..s }
+        };
+        let points = |points: &HashSet<(u8, u8)>| points.iter().copied().map(point).collect();
+        let pairs = |pairs: &HashSet<(_, _)>| {
+            pairs
+                .iter()
+                .map(|&(p1, p2)| (point(p1), point(p2)))
+                .collect()
+        };
+        Self {
+            size: transform.size(size),
+            stones: self.stones.iter().map(|(&p, &c)| (point(p), c)).collect(),
+            stones_before_move: self
+                .stones_before_move
+                .iter()
+                .map(|(&n, stones)| (n, stones.iter().copied().map(stone).collect()))
+                .collect(),
+            moves: self.moves.iter().map(|&(n, s)| (n, stone(s))).collect(),
+            marks: points(&self.marks),
+            triangles: points(&self.triangles),
+            circles: points(&self.circles),
+            squares: points(&self.squares),
+            selected: points(&self.selected),
+            lines: pairs(&self.lines),
+            arrows: pairs(&self.arrows),
+            dimmed: points(&self.dimmed),
+            labels: self
+                .labels
+                .iter()
+                .map(|(&p, text)| (point(p), text.clone()))
+                .collect(),
+            territory: self
+                .territory
+                .iter()
+                .map(|(&p, &c)| (point(p), c))
+                .collect(),
+            next_moves: self
+                .next_moves
+                .iter()
+                .map(|&(branch, s)| (branch, stone(s)))
+                .collect(),
+            analysis: self
+                .analysis
+                .as_ref()
+                .map(|analysis| analysis.transformed(transform, size)),
+            ..self.clone()
+        }
+    }
+
     pub fn hoshi_points(&self) -> impl Iterator<Item = (u8, u8)> {
         match self.size {
             (9, 9) => Self::NINE_HOSHIS.iter().copied(),
@@ -151,7 +313,30 @@ impl Goban {
         self.labels.iter()
     }
 
-    fn new(board_size: (u8, u8)) -> Self {
+    /// Returns the moves played in the node's child variations, with their branch numbers.
+    ///
+    /// Branch 0 is the main line. Children without a move are skipped.
+    pub fn next_moves(&self) -> impl Iterator<Item = (usize, Stone)> + '_ {
+        self.next_moves.iter().copied()
+    }
+
+    /// Returns the annotations (`TE`, `BM`, `IT`, `DO`, `GB`, `GW`, `DM`, `UC`) on the node where
+    /// the move was played, along with position judgments from later nodes without a move.
+    ///
+    /// Move 0 holds the position judgments made before the first move.
+    pub fn annotations(&self, move_number: u64) -> &[Annotation] {
+        self.annotations
+            .get(&move_number)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns engine analysis stored on the current node, if any.
+    pub fn analysis(&self) -> Option<&Analysis> {
+        self.analysis.as_ref()
+    }
+
+    fn new(board_size: (u8, u8), goban_options: GobanOptions) -> Self {
         Self {
             size: board_size,
             stones: HashMap::new(),
@@ -167,6 +352,18 @@ impl Goban {
             arrows: HashSet::new(),
             dimmed: HashSet::new(),
             labels: HashMap::new(),
+            territory: HashMap::new(),
+            komi: None,
+            result: None,
+            comment: None,
+            game_info: GameInfo::default(),
+            captures: Vec::new(),
+            goban_options,
+            position_history: Vec::new(),
+            rule_violations: Vec::new(),
+            next_moves: Vec::new(),
+            analysis: None,
+            annotations: HashMap::new(),
         }
     }
 
@@ -180,18 +377,28 @@ impl Goban {
         self.labels.clear();
         self.lines.clear();
         self.arrows.clear();
+        self.territory.clear();
+        self.comment = None;
+        let moves_played = self.moves.len();
+        let mut annotations = vec![];
         for prop in sgf_node.properties() {
             match prop {
                 go::Prop::B(go::Move::Move(point)) => {
-                    if !self.is_tt_pass(*point) {
+                    if !is_tt_pass(*point, self.size) {
                         self.play_stone(Stone::new(point.x, point.y, StoneColor::Black))?;
+                    } else {
+                        self.record_pass();
                     }
                 }
+                go::Prop::B(go::Move::Pass) => self.record_pass(),
                 go::Prop::W(go::Move::Move(point)) => {
-                    if !self.is_tt_pass(*point) {
+                    if !is_tt_pass(*point, self.size) {
                         self.play_stone(Stone::new(point.x, point.y, StoneColor::White))?;
+                    } else {
+                        self.record_pass();
                     }
                 }
+                go::Prop::W(go::Move::Pass) => self.record_pass(),
                 go::Prop::AB(points) => {
                     for point in points.iter() {
                         self.add_stone(Stone::new(point.x, point.y, StoneColor::Black))?;
@@ -234,9 +441,61 @@ impl Goban {
                         .map(|(p1, p2)| ((p1.x, p1.y), (p2.x, p2.y)))
                         .collect()
                 }
+                go::Prop::TB(points) => self
+                    .territory
+                    .extend(points.iter().map(|p| ((p.x, p.y), StoneColor::Black))),
+                go::Prop::TW(points) => self
+                    .territory
+                    .extend(points.iter().map(|p| ((p.x, p.y), StoneColor::White))),
+                go::Prop::KM(komi) => self.komi = Some(*komi),
+                go::Prop::RE(result) => self.result = Some(result.to_string()),
+                go::Prop::C(comment) => self.comment = Some(comment.to_string()),
+                go::Prop::PB(name) => self.game_info.black_player = Some(name.to_string()),
+                go::Prop::PW(name) => self.game_info.white_player = Some(name.to_string()),
+                go::Prop::BR(rank) => self.game_info.black_rank = Some(rank.to_string()),
+                go::Prop::WR(rank) => self.game_info.white_rank = Some(rank.to_string()),
+                go::Prop::EV(event) => self.game_info.event = Some(event.to_string()),
+                go::Prop::DT(date) => self.game_info.date = Some(date.to_string()),
+                go::Prop::TE(double) => annotations.push(match double {
+                    Double::One => Annotation::GoodMove,
+                    Double::Two => Annotation::VeryGoodMove,
+                }),
+                go::Prop::BM(double) => annotations.push(match double {
+                    Double::One => Annotation::BadMove,
+                    Double::Two => Annotation::VeryBadMove,
+                }),
+                go::Prop::IT => annotations.push(Annotation::Interesting),
+                go::Prop::DO => annotations.push(Annotation::Doubtful),
+                go::Prop::GB(_) => annotations.push(Annotation::GoodForBlack),
+                go::Prop::GW(_) => annotations.push(Annotation::GoodForWhite),
+                go::Prop::DM(_) => annotations.push(Annotation::Even),
+                go::Prop::UC(_) => annotations.push(Annotation::Unclear),
                 _ => {}
             }
         }
+        // Annotations are attached to the move played at the node. Nodes without a move can
+        // only judge the position, which is attached to the last move played.
+        if self.moves.len() == moves_played {
+            annotations.retain(Annotation::is_position_judgment);
+        }
+        if !annotations.is_empty() {
+            let move_number = self.moves.last().map_or(0, |(n, _)| *n);
+            let existing = self.annotations.entry(move_number).or_default();
+            for annotation in annotations {
+                if !existing.contains(&annotation) {
+                    existing.push(annotation);
+                }
+            }
+        }
+        self.next_moves = sgf_node
+            .children
+            .iter()
+            .enumerate()
+            .filter_map(|(branch, child)| Some((branch, self.node_move(child)?)))
+            .collect();
+        if self.goban_options.sgf_analysis {
+            self.analysis = Analysis::from_sgf_node(sgf_node, self.size);
+        }
 
         Ok(())
     }
@@ -253,7 +512,7 @@ impl Goban {
 
     fn play_stone(&mut self, stone: Stone) -> Result<(), GobanError> {
         self.stones_before_move.insert(
-            self.move_number,
+            self.move_number + 1,
             self.stones
                 .iter()
                 .map(|(point, color)| Stone {
@@ -263,28 +522,93 @@ impl Goban {
                 })
                 .collect(),
         );
+        let move_number = self.move_number + 1;
+        let key = (stone.x, stone.y);
+        let violation_count = self.rule_violations.len();
+        if self.goban_options.check_rules {
+            let position_before = self.position();
+            if self.position_history.last() != Some(&position_before) {
+                self.position_history.push(position_before);
+            }
+        }
+        if self.stones.contains_key(&key) {
+            self.rule_violations.push(RuleViolation::OccupiedPoint {
+                move_number,
+                point: key,
+            });
+        }
         self.add_stone(stone)?;
         let opponent_color = match stone.color {
             StoneColor::Black => StoneColor::White,
             StoneColor::White => StoneColor::Black,
         };
         // Remove any neighboring groups with no liberties.
-        let key = (stone.x, stone.y);
+        let mut captured = 0;
         for neighbor in self.neighbors(key) {
             if let Some(color) = self.stones.get(&neighbor) {
                 if *color == opponent_color {
-                    self.process_captures(neighbor);
+                    captured += self.process_captures(neighbor);
                 }
             }
         }
+        if captured > 0 {
+            self.captures.push((move_number, stone.color, captured));
+        }
         // Now remove the played stone if still neccessary
-        self.process_captures(key);
+        let suicided = self.process_captures(key);
+        if suicided > 0 {
+            self.captures.push((move_number, opponent_color, suicided));
+        }
+        if !self.stones.contains_key(&key) {
+            self.rule_violations.push(RuleViolation::Suicide {
+                move_number,
+                point: key,
+            });
+        }
+        if self.goban_options.check_rules {
+            let position_after = self.position();
+            let history_len = self.position_history.len();
+            // Only check for repeated positions if the move was otherwise legal.
+            if self.rule_violations.len() == violation_count {
+                if history_len >= 2 && self.position_history[history_len - 2] == position_after {
+                    self.rule_violations.push(RuleViolation::Ko {
+                        move_number,
+                        point: key,
+                    });
+                } else if self.position_history.contains(&position_after) {
+                    self.rule_violations.push(RuleViolation::Superko {
+                        move_number,
+                        point: key,
+                    });
+                }
+            }
+            self.position_history.push(position_after);
+        }
         self.move_number += 1;
         self.moves.push((self.move_number, stone));
 
         Ok(())
     }
 
+    /// Repeats the current position in the history, so a ko retaken after a pass isn't seen as
+    /// an immediate recapture.
+    fn record_pass(&mut self) {
+        if self.goban_options.check_rules {
+            let position = self.position();
+            if self.position_history.last() != Some(&position) {
+                self.position_history.push(position.clone());
+            }
+            self.position_history.push(position);
+        }
+    }
+
+    /// Returns the current whole board position, sorted so positions can be compared.
+    fn position(&self) -> Vec<Stone> {
+        let mut stones: Vec<Stone> = self.stones().collect();
+        stones.sort_unstable_by_key(|stone| (stone.x, stone.y));
+        stones
+    }
+
     fn clear_point(&mut self, point: (u8, u8)) {
         self.stones.remove(&point);
     }
@@ -293,7 +617,7 @@ impl Goban {
         self.move_number = num;
     }
 
-    fn neighbors(&self, point: (u8, u8)) -> impl Iterator<Item = (u8, u8)> {
+    pub(crate) fn neighbors(&self, point: (u8, u8)) -> impl Iterator<Item = (u8, u8)> {
         let (x, y) = point;
         let mut neighbors = vec![];
         if x < self.size.0 - 1 {
@@ -312,10 +636,12 @@ impl Goban {
         neighbors.into_iter()
     }
 
-    fn process_captures(&mut self, start_point: (u8, u8)) {
+    /// Removes the group at `start_point` if it has no liberties, returning the number of stones
+    /// removed.
+    fn process_captures(&mut self, start_point: (u8, u8)) -> u64 {
         let group_color = match self.stones.get(&start_point) {
             Some(color) => color,
-            None => return,
+            None => return 0,
         };
         let mut group = HashSet::new();
         let mut to_process = VecDeque::new();
@@ -327,7 +653,7 @@ impl Goban {
                     continue;
                 }
                 match self.stones.get(&neighbor) {
-                    None => return,
+                    None => return 0,
                     Some(c) if c == group_color => {
                         to_process.push_back(neighbor);
                     }
@@ -335,16 +661,92 @@ impl Goban {
                 }
             }
         }
-        for stone in group {
-            self.stones.remove(&stone);
+        for stone in &group {
+            self.stones.remove(stone);
         }
+        group.len() as u64
+    }
+
+    /// Returns the stone played in a node, if any.
+    fn node_move(&self, sgf_node: &SgfNode<go::Prop>) -> Option<Stone> {
+        sgf_node.properties().find_map(|prop| match prop {
+            go::Prop::B(go::Move::Move(point)) if !is_tt_pass(*point, self.size) => {
+                Some(Stone::new(point.x, point.y, StoneColor::Black))
+            }
+            go::Prop::W(go::Move::Move(point)) if !is_tt_pass(*point, self.size) => {
+                Some(Stone::new(point.x, point.y, StoneColor::White))
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Players and event information from the SGF game info properties.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct GameInfo {
+    pub black_player: Option<String>,
+    pub white_player: Option<String>,
+    pub black_rank: Option<String>,
+    pub white_rank: Option<String>,
+    pub event: Option<String>,
+    pub date: Option<String>,
+}
+
+/// An evaluation of a move from the SGF move annotation and position judgment properties.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Annotation {
+    GoodMove,
+    VeryGoodMove,
+    BadMove,
+    VeryBadMove,
+    Interesting,
+    Doubtful,
+    GoodForBlack,
+    GoodForWhite,
+    Even,
+    Unclear,
+}
+
+impl Annotation {
+    /// Returns whether the annotation judges the position (`GB`, `GW`, `DM`, `UC`) rather than
+    /// the move.
+    pub fn is_position_judgment(&self) -> bool {
+        matches!(
+            self,
+            Annotation::GoodForBlack
+                | Annotation::GoodForWhite
+                | Annotation::Even
+                | Annotation::Unclear
+        )
     }
 
-    fn is_tt_pass(&self, point: go::Point) -> bool {
-        point.x == 19 && point.y == 19 && self.size.0 < 20 && self.size.1 < 20
+    /// Returns the glyph drawn for the annotation unless the style overrides it.
+    pub fn default_symbol(&self) -> &'static str {
+        match self {
+            Annotation::GoodMove => "!",
+            Annotation::VeryGoodMove => "!!",
+            Annotation::BadMove => "?",
+            Annotation::VeryBadMove => "??",
+            Annotation::Interesting => "!?",
+            Annotation::Doubtful => "?!",
+            Annotation::GoodForBlack => "B+",
+            Annotation::GoodForWhite => "W+",
+            Annotation::Even => "=",
+            Annotation::Unclear => "∞",
+        }
     }
 }
 
+/// Optional tracking done while playing through a game.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GobanOptions {
+    /// Keep the position history needed to detect ko and superko.
+    pub check_rules: bool,
+    /// Parse engine analysis stored in node comments and properties.
+    pub sgf_analysis: bool,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum StoneColor {
     Black,
@@ -364,7 +766,29 @@ impl Stone {
     }
 }
 
-fn get_board_size(sgf_node: &SgfNode<go::Prop>) -> Result<(u8, u8), GobanError> {
+/// Parses an SGF collection.
+///
+/// Games aren't checked to be games of Go until they're selected with [`game_root`].
+pub fn parse_collection(sgf: &str, strict: bool) -> Result<Vec<GameTree>, GobanError> {
+    let parse_options = ParseOptions {
+        lenient: !strict,
+        ..Default::default()
+    };
+    Ok(sgf_parse::parse_with_options(sgf, &parse_options)?)
+}
+
+/// Returns the root node of the selected game, failing if it isn't a game of Go.
+pub fn game_root(
+    collection: &[GameTree],
+    game_number: u64,
+) -> Result<&SgfNode<go::Prop>, GobanError> {
+    Ok(collection
+        .get(game_number as usize)
+        .ok_or(GobanError::MissingGame)?
+        .as_go_node()?)
+}
+
+pub(crate) fn get_board_size(sgf_node: &SgfNode<go::Prop>) -> Result<(u8, u8), GobanError> {
     match sgf_node.get_property("SZ") {
         Some(go::Prop::SZ((x, y))) if *x <= 52 && *y <= 52 => Ok((*x, *y)),
         None => Ok((19, 19)),
@@ -372,11 +796,20 @@ fn get_board_size(sgf_node: &SgfNode<go::Prop>) -> Result<(u8, u8), GobanError>
     }
 }
 
+/// Returns whether a move at `tt` is a pass, which it is on boards up to 19x19.
+pub(crate) fn is_tt_pass(point: go::Point, board_size: (u8, u8)) -> bool {
+    point.x == 19 && point.y == 19 && board_size.0 < 20 && board_size.1 < 20
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::errors::GobanError;
+    use crate::errors::{GobanError, RuleViolation};
 
-    use super::{get_board_size, Goban};
+    use crate::render::{NodeDescription, NodeNumber, Transform};
+
+    use super::{
+        get_board_size, parse_collection, Annotation, Goban, GobanOptions, Stone, StoneColor,
+    };
 
     #[test]
     fn play_over_existing_stone() {
@@ -392,6 +825,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::nonminimal_bool)]
     fn strict_parsing_fails_for_bad_sgf() {
         let result = Goban::from_sgf("(;AB[ac];B[ac]", &Default::default(), true);
         assert!(!result.is_ok());
@@ -402,4 +836,270 @@ mod tests {
         let result = Goban::from_sgf("(;AB[ac];B[ac]", &Default::default(), false);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn batch_matches_individual_gobans() {
+        let sgf = "(;SZ[9];B[ee];W[ec](;B[cc];W[gc])(;B[gg]))";
+        let node_descriptions: Vec<NodeDescription> = ["0:2", "1:last", "0:1", "0:last"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let collection = parse_collection(sgf, true).unwrap();
+        let gobans =
+            Goban::from_collection(&collection, &node_descriptions, &Default::default()).unwrap();
+        for (goban, node_description) in gobans.iter().zip(&node_descriptions) {
+            let expected = Goban::from_sgf(sgf, node_description, true).unwrap();
+            let mut stones: Vec<_> = goban.stones().map(|s| (s.x, s.y)).collect();
+            let mut expected_stones: Vec<_> = expected.stones().map(|s| (s.x, s.y)).collect();
+            stones.sort_unstable();
+            expected_stones.sort_unstable();
+            assert_eq!(stones, expected_stones);
+        }
+    }
+
+    #[test]
+    fn batch_insufficient_nodes() {
+        let collection = parse_collection("(;B[ee];W[ec])", true).unwrap();
+        let node_descriptions = [NodeDescription {
+            node_number: NodeNumber::Number(3),
+            ..Default::default()
+        }];
+        let result = Goban::from_collection(&collection, &node_descriptions, &Default::default());
+        assert!(matches!(result, Err(GobanError::InsufficientSgfNodes)));
+    }
+
+    #[test]
+    fn only_selected_games_must_be_go() {
+        let sgf = "(;GM[3];B[aa])(;SZ[9];B[ee])";
+        let game = |game_number| NodeDescription {
+            game_number,
+            ..Default::default()
+        };
+        assert!(Goban::from_sgf(sgf, &game(1), true).is_ok());
+        assert!(Goban::from_sgf(sgf, &game(0), true).is_err());
+    }
+
+    #[test]
+    fn stones_before_move() {
+        let goban =
+            Goban::from_sgf("(;AB[aa];W[ba];B[ee];W[ab])", &Default::default(), true).unwrap();
+        let mut stones: Vec<_> = goban.stones_before_move(3).map(|s| (s.x, s.y)).collect();
+        stones.sort_unstable();
+        assert_eq!(stones, vec![(0, 0), (1, 0), (4, 4)]);
+        assert_eq!(goban.stones_before_move(1).count(), 1);
+    }
+
+    #[test]
+    fn legal_game_has_no_violations() {
+        let goban =
+            Goban::from_sgf("(;B[aa];W[ba];B[ee];W[ab])", &Default::default(), true).unwrap();
+        assert_eq!(goban.rule_violations().count(), 0);
+        assert!(goban.check_rules().is_ok());
+    }
+
+    #[test]
+    fn occupied_point_violation() {
+        let goban = Goban::from_sgf("(;AB[ac];B[ac])", &Default::default(), true).unwrap();
+        let violations: Vec<_> = goban.rule_violations().collect();
+        assert_eq!(
+            violations,
+            vec![RuleViolation::OccupiedPoint {
+                move_number: 1,
+                point: (0, 2)
+            }]
+        );
+        assert!(matches!(
+            goban.check_rules(),
+            Err(GobanError::RuleViolation(
+                RuleViolation::OccupiedPoint { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn suicide_violation() {
+        let goban = Goban::from_sgf("(;AW[ba][ab];B[aa])", &Default::default(), true).unwrap();
+        let violations: Vec<_> = goban.rule_violations().collect();
+        assert_eq!(
+            violations,
+            vec![RuleViolation::Suicide {
+                move_number: 1,
+                point: (0, 0)
+            }]
+        );
+    }
+
+    fn checked_goban(sgf: &str) -> Goban {
+        let collection = parse_collection(sgf, true).unwrap();
+        let goban_options = GobanOptions {
+            check_rules: true,
+            ..Default::default()
+        };
+        let mut gobans =
+            Goban::from_collection(&collection, &[NodeDescription::default()], &goban_options)
+                .unwrap();
+        gobans.pop().unwrap()
+    }
+
+    #[test]
+    fn ko_violation() {
+        // White takes the ko at bb, then black immediately retakes at cb.
+        let sgf = "(;AB[ba][ab][bc][cb]AW[ca][cc][db];W[bb];B[cb])";
+        let goban = checked_goban(sgf);
+        let violations: Vec<_> = goban.rule_violations().collect();
+        assert_eq!(
+            violations,
+            vec![RuleViolation::Ko {
+                move_number: 2,
+                point: (2, 1)
+            }]
+        );
+    }
+
+    #[test]
+    fn ko_after_passes() {
+        // Both players pass before black retakes, repeating the position from before the take.
+        let sgf = "(;AB[ba][ab][bc][cb]AW[ca][cc][db];W[bb];B[];W[];B[cb])";
+        let goban = checked_goban(sgf);
+        let violations: Vec<_> = goban.rule_violations().collect();
+        assert_eq!(
+            violations,
+            vec![RuleViolation::Superko {
+                move_number: 2,
+                point: (2, 1)
+            }]
+        );
+        let goban = checked_goban("(;AB[ba][ab][bc][cb]AW[ca][cc][db];W[bb];B[tt];W[];B[cb])");
+        assert_eq!(goban.rule_violations().count(), 1);
+    }
+
+    #[test]
+    fn ko_ignored_without_rule_checks() {
+        let sgf = "(;AB[ba][ab][bc][cb]AW[ca][cc][db];W[bb];B[cb])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        assert_eq!(goban.rule_violations().count(), 0);
+    }
+
+    #[test]
+    fn prisoners() {
+        let sgf = "(;AB[ba][ab][bc][cb]AW[ca][cc][db];W[bb];B[cb];W[aa])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let captures: Vec<_> = goban.captures().collect();
+        assert_eq!(
+            captures,
+            vec![
+                (1, StoneColor::White, 1),
+                (2, StoneColor::Black, 1),
+                (3, StoneColor::Black, 1),
+            ]
+        );
+        assert_eq!(goban.prisoners(StoneColor::Black), 2);
+        assert_eq!(goban.prisoners(StoneColor::White), 1);
+    }
+
+    #[test]
+    fn transformed() {
+        let sgf = "(;SZ[19:13]AB[aa];W[cb]TR[ab]LB[da:A]AR[aa:cb])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let transform = Transform {
+            rotate: 90,
+            ..Default::default()
+        };
+        let goban = goban.transformed(&transform);
+        assert_eq!(goban.size(), (13, 19));
+        let mut stones: Vec<_> = goban.stones().collect();
+        stones.sort_by_key(|s| (s.x, s.y));
+        assert_eq!(
+            stones,
+            vec![
+                Stone::new(11, 2, StoneColor::White),
+                Stone::new(12, 0, StoneColor::Black),
+            ]
+        );
+        let moves: Vec<_> = goban.moves().collect();
+        assert_eq!(moves, vec![(1, Stone::new(11, 2, StoneColor::White))]);
+        assert_eq!(goban.triangles().collect::<Vec<_>>(), vec![(11, 0)]);
+        assert_eq!(goban.labels().next(), Some((&(12, 3), &"A".to_string())));
+        assert_eq!(goban.arrows().collect::<Vec<_>>(), vec![((12, 0), (11, 2))]);
+    }
+
+    #[test]
+    fn next_moves() {
+        let sgf = "(;SZ[9];B[ee](;W[cc])(;AB[gg])(;W[tt])(;W[dc]))";
+        let node_description = NodeDescription {
+            node_number: NodeNumber::Number(1),
+            ..Default::default()
+        };
+        let goban = Goban::from_sgf(sgf, &node_description, true).unwrap();
+        assert_eq!(
+            goban.next_moves().collect::<Vec<_>>(),
+            vec![
+                (0, Stone::new(2, 2, StoneColor::White)),
+                (3, Stone::new(3, 2, StoneColor::White)),
+            ]
+        );
+        let last = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        assert_eq!(last.next_moves().next(), None);
+    }
+
+    #[test]
+    fn annotations() {
+        let sgf = "(;SZ[9]GB[1];B[ee]TE[2]UC[1];W[dc]IT[];B[cc];W[dd]BM[1])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        assert_eq!(goban.annotations(0), &[Annotation::GoodForBlack]);
+        assert_eq!(
+            goban.annotations(1),
+            &[Annotation::VeryGoodMove, Annotation::Unclear]
+        );
+        assert_eq!(goban.annotations(2), &[Annotation::Interesting]);
+        assert_eq!(goban.annotations(3), &[]);
+        assert_eq!(goban.annotations(4), &[Annotation::BadMove]);
+    }
+
+    #[test]
+    fn position_judgments_without_moves() {
+        // The comment node's judgment goes with the last move, and its TE is dropped.
+        let sgf = "(;SZ[9];B[ee]TE[1];C[Black is ahead]GB[1]TE[1];W[dc])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        assert_eq!(
+            goban.annotations(1),
+            &[Annotation::GoodMove, Annotation::GoodForBlack]
+        );
+        assert_eq!(goban.annotations(2), &[]);
+    }
+
+    #[test]
+    fn analysis() {
+        let sgf = "(;SZ[9]C[E5: 52% 100 visits];B[ee];W[dc])";
+        let collection = parse_collection(sgf, true).unwrap();
+        let goban_options = GobanOptions {
+            sgf_analysis: true,
+            ..Default::default()
+        };
+        let node_descriptions = [
+            NodeDescription {
+                node_number: NodeNumber::Number(0),
+                ..Default::default()
+            },
+            Default::default(),
+        ];
+        let gobans =
+            Goban::from_collection(&collection, &node_descriptions, &goban_options).unwrap();
+        let candidates = &gobans[0].analysis().unwrap().candidates;
+        assert_eq!(candidates[0].point, (4, 4));
+        assert!(gobans[1].analysis().is_none());
+        let root = Goban::from_sgf(sgf, &node_descriptions[0], true).unwrap();
+        assert!(root.analysis().is_none());
+    }
+
+    #[test]
+    fn dead_stones() {
+        let sgf = "(;SZ[5]KM[0.5]RE[B+3.5]AB[ca][cb][cc][cd][ce][ed]AW[da][db][dc][dd][de]\
+            TB[aa][ba][ca][ab][bb][ac][bc][ad][bd][ae][be]TW[ea][eb][ec][ed][ee])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let dead_stones: Vec<_> = goban.dead_stones().map(|s| (s.x, s.y)).collect();
+        // The black stone at ca is in black's own territory, so isn't dead.
+        assert_eq!(dead_stones, vec![(4, 3)]);
+        assert_eq!(goban.result(), Some("B+3.5"));
+    }
 }
@@ -1,11 +1,28 @@
+mod analysis;
 mod args;
+mod book;
 mod errors;
 mod goban;
+mod node_selector;
 mod query;
 mod render;
+mod scoring;
 mod sgf_traversal;
 
-pub use args::{Command, OutputFormat, QueryArgs, QueryMode, RenderArgs, SgfRenderArgs};
-pub use goban::Goban;
+pub use analysis::{Analysis, Candidate};
+pub use args::{
+    BookArgs, Command, OutputFormat, QueryArgs, QueryMode, RenderArgs, RuleCheckMode, SgfRenderArgs,
+};
+pub use book::{read_problems, render_book, BookOptions, Problem, SolutionPlacement};
+pub use errors::{GobanError, RuleViolation};
+pub use goban::{game_root, parse_collection, Annotation, GameInfo, Goban, GobanOptions};
+pub use node_selector::{NodeSelector, TreePath};
 pub use query::query;
-pub use render::{svg, text, RenderOptions};
+pub use render::animation::AnimationOptions;
+pub use render::grid::GridOptions;
+pub use render::overlay::VariationOverlay;
+pub use render::{
+    animation, grid, html, latex, overlay, sensei, svg, text, NodeDescription, NodeNumber,
+    RenderOptions, Transform, VariationMarkup,
+};
+pub use scoring::{score, Score, ScoringRules};
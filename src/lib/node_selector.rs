@@ -0,0 +1,221 @@
+use regex::Regex;
+use sgf_parse::{
+    go::{Move, Prop},
+    GameTree, SgfNode,
+};
+
+use crate::errors::{GobanError, UsageError};
+use crate::goban::{game_root, get_board_size, is_tt_pass};
+use crate::render::{NodeDescription, NodeNumber};
+use crate::sgf_traversal::{variation_nodes, SgfTraversal};
+
+/// A way of finding a node that doesn't depend on its variation and node numbers.
+#[derive(Debug, Clone)]
+pub enum NodeSelector {
+    /// The node with this name (`N`).
+    Name(String),
+    /// The node where this move is played in the selected variation.
+    Move(u64),
+    /// The node whose comment (`C`) matches the regex.
+    CommentMatches(Regex),
+    /// The variation reached by taking these branches at each fork in turn.
+    TreePath(TreePath),
+}
+
+/// Branches to take at successive forks in the game tree (e.g. '0.2.1').
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreePath(pub Vec<usize>);
+
+impl std::str::FromStr for TreePath {
+    type Err = UsageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim()
+            .split('.')
+            .map(|branch| {
+                branch
+                    .parse()
+                    .map_err(|_| UsageError::InvalidTreePath(s.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(TreePath)
+    }
+}
+
+impl NodeSelector {
+    /// Returns the node the selector finds in the selected game.
+    ///
+    /// Moves are looked up in the selected variation, and tree paths select a variation, leaving
+    /// the node number as is. Names and comments are searched for in every variation, and must
+    /// match exactly one node.
+    pub fn resolve(
+        &self,
+        collection: &[GameTree],
+        node_description: &NodeDescription,
+    ) -> Result<NodeDescription, GobanError> {
+        let game_number = node_description.game_number;
+        let root = game_root(collection, game_number)?;
+        let found = |variation, node_number| NodeDescription {
+            game_number,
+            variation,
+            node_number: NodeNumber::Number(node_number),
+        };
+        let search = |is_match: &dyn Fn(&Prop) -> bool| {
+            let matches: Vec<NodeDescription> = SgfTraversal::new(root)
+                .filter(|node| node.sgf_node.properties().any(is_match))
+                .map(|node| found(node.variation, node.variation_node_number))
+                .collect();
+            match matches[..] {
+                [] => Err(GobanError::NoMatchingNode),
+                [node_description] => Ok(node_description),
+                _ => Err(GobanError::AmbiguousNodeSelector(matches)),
+            }
+        };
+        match self {
+            Self::Name(name) => search(&|prop| matches!(prop, Prop::N(text) if text.text == *name)),
+            Self::CommentMatches(regex) => {
+                search(&|prop| matches!(prop, Prop::C(text) if regex.is_match(&text.text)))
+            }
+            Self::Move(move_number) => {
+                let node_number = move_node(root, node_description.variation, *move_number)?;
+                Ok(found(node_description.variation, node_number))
+            }
+            Self::TreePath(path) => Ok(NodeDescription {
+                variation: path_variation(root, path)?,
+                ..*node_description
+            }),
+        }
+    }
+}
+
+/// Returns the number of the node in the variation where the move is played.
+///
+/// Moves are numbered as in diagrams: passes aren't counted, and `MN` sets the move number.
+fn move_node(root: &SgfNode<Prop>, variation: u64, move_number: u64) -> Result<u64, GobanError> {
+    let size = get_board_size(root)?;
+    let mut current = 0;
+    for node in variation_nodes(root, variation)? {
+        for prop in node.sgf_node.properties() {
+            match prop {
+                Prop::MN(n) => current = *n as u64,
+                Prop::B(Move::Move(point)) | Prop::W(Move::Move(point))
+                    if !is_tt_pass(*point, size) =>
+                {
+                    current += 1;
+                    if current == move_number {
+                        return Ok(node.variation_node_number);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Err(GobanError::NoMatchingNode)
+}
+
+/// Returns the variation reached by following the path's branches from the root.
+fn path_variation(root: &SgfNode<Prop>, path: &TreePath) -> Result<u64, GobanError> {
+    let mut sgf_node = root;
+    for &branch in &path.0 {
+        while sgf_node.children.len() == 1 {
+            sgf_node = &sgf_node.children[0];
+        }
+        sgf_node = sgf_node
+            .children
+            .get(branch)
+            .ok_or(GobanError::NoMatchingNode)?;
+    }
+    SgfTraversal::new(root)
+        .find(|node| std::ptr::eq(node.sgf_node, sgf_node))
+        .map(|node| node.variation)
+        .ok_or(GobanError::NoMatchingNode)
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use crate::errors::GobanError;
+    use crate::render::{NodeDescription, NodeNumber};
+
+    use super::{NodeSelector, TreePath};
+
+    // Variation 1 starts at node 4, and variation 2 at node 2.
+    static TEST_DATA: &str = "(;SZ[9]C[Root];B[ee]N[Opening]\
+                              (;W[cc];B[dd]\
+                              (;W[tt];B[gg]C[Failure])\
+                              (;W[dc]C[Success: Black lives];B[gg]))\
+                              (;W[ge];MN[10]B[cc]C[Failure]))";
+
+    fn resolve(selector: NodeSelector) -> Result<NodeDescription, GobanError> {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        selector.resolve(&collection, &Default::default())
+    }
+
+    fn node(variation: u64, node_number: u64) -> NodeDescription {
+        NodeDescription {
+            game_number: 0,
+            variation,
+            node_number: NodeNumber::Number(node_number),
+        }
+    }
+
+    #[test]
+    fn name() {
+        let result = resolve(NodeSelector::Name("Opening".to_string()));
+        assert_eq!(result.unwrap(), node(0, 1));
+        let result = resolve(NodeSelector::Name("Middle game".to_string()));
+        assert!(matches!(result, Err(GobanError::NoMatchingNode)));
+    }
+
+    #[test]
+    fn comment_matches() {
+        let regex = Regex::new("^Success").unwrap();
+        let result = resolve(NodeSelector::CommentMatches(regex));
+        assert_eq!(result.unwrap(), node(1, 4));
+        let regex = Regex::new("Failure").unwrap();
+        let result = resolve(NodeSelector::CommentMatches(regex));
+        assert!(matches!(
+            result,
+            Err(GobanError::AmbiguousNodeSelector(matches)) if matches == vec![node(0, 5), node(2, 3)]
+        ));
+    }
+
+    #[test]
+    fn move_number() {
+        // The pass at node 4 isn't counted.
+        assert_eq!(resolve(NodeSelector::Move(4)).unwrap(), node(0, 5));
+        assert!(matches!(
+            resolve(NodeSelector::Move(5)),
+            Err(GobanError::NoMatchingNode)
+        ));
+    }
+
+    #[test]
+    fn move_number_in_variation() {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        let node_description = NodeDescription {
+            variation: 2,
+            ..Default::default()
+        };
+        let result = NodeSelector::Move(11).resolve(&collection, &node_description);
+        assert_eq!(result.unwrap(), node(2, 3));
+    }
+
+    #[test]
+    fn tree_path() {
+        let path = |s: &str| NodeSelector::TreePath(s.parse().unwrap());
+        let last = |variation| NodeDescription {
+            variation,
+            ..Default::default()
+        };
+        assert_eq!(resolve(path("0")).unwrap(), last(0));
+        assert_eq!(resolve(path("0.1")).unwrap(), last(1));
+        assert_eq!(resolve(path("1")).unwrap(), last(2));
+        assert!(matches!(
+            resolve(path("1.0")),
+            Err(GobanError::NoMatchingNode)
+        ));
+        assert!("0.a".parse::<TreePath>().is_err());
+    }
+}
@@ -1,14 +1,20 @@
+use std::collections::{BTreeMap, HashSet};
 use std::io::{stdout, Write};
 
-use sgf_parse::{go::Prop, SgfNode};
+use sgf_parse::{
+    go::{Move, Prop},
+    GameTree, SgfNode,
+};
 
-use crate::errors::QueryError;
-use crate::sgf_traversal::{variation_nodes, variation_roots, SgfTraversalNode};
-use crate::{QueryArgs, QueryMode};
+use crate::errors::{sgf_coordinate, QueryError};
+use crate::goban::{game_root, get_board_size, is_tt_pass, GameInfo, GobanOptions};
+use crate::sgf_traversal::{variation_nodes, variation_roots, SgfTraversal, SgfTraversalNode};
+use crate::{Goban, NodeDescription, NodeNumber, QueryArgs, QueryMode, ScoringRules};
 
 pub fn query(sgf: &str, query_args: &QueryArgs) -> Result<(), QueryError> {
     let collection = sgf_parse::go::parse(sgf)?;
     match query_args.mode() {
+        QueryMode::Default if query_args.json => write_query_json(&collection, stdout())?,
         QueryMode::Default => write_query_text(&collection, stdout())?,
         QueryMode::LastGame => println!("{}", query_game_index(&collection)?),
         QueryMode::LastVariation => println!(
@@ -19,10 +25,18 @@ pub fn query(sgf: &str, query_args: &QueryArgs) -> Result<(), QueryError> {
             "{}",
             query_node_index(&collection, query_args.game_number, query_args.variation)?
         ),
+        QueryMode::Score => write_score(&game_trees(collection), query_args, stdout())?,
+        QueryMode::Info if query_args.json => write_info_json(&game_trees(collection), stdout())?,
+        QueryMode::Info => write_info_text(&game_trees(collection), stdout())?,
     }
     Ok(())
 }
 
+/// Wraps the parsed games for building gobans from them.
+fn game_trees(collection: Vec<SgfNode<Prop>>) -> Vec<GameTree> {
+    collection.into_iter().map(GameTree::GoGame).collect()
+}
+
 fn query_game_index(collection: &[SgfNode<Prop>]) -> Result<usize, QueryError> {
     match collection.len() {
         0 => Err(QueryError::GameNotFound),
@@ -60,6 +74,251 @@ fn query_node_index(
     }
 }
 
+fn write_score(
+    collection: &[GameTree],
+    query_args: &QueryArgs,
+    mut writer: impl Write,
+) -> Result<(), QueryError> {
+    let node_description = NodeDescription {
+        game_number: query_args.game_number,
+        variation: query_args.variation,
+        node_number: NodeNumber::Last,
+    };
+    let goban = Goban::from_collection(
+        collection,
+        std::slice::from_ref(&node_description),
+        &GobanOptions::default(),
+    )?
+    .pop()
+    .unwrap();
+    let dead_stones: HashSet<(u8, u8)> = match &query_args.dead_stones {
+        Some(points) => points.iter().copied().collect(),
+        None => goban.dead_stones().map(|s| (s.x, s.y)).collect(),
+    };
+    let score = crate::score(&goban, &dead_stones, query_args.rules);
+    let rules = match query_args.rules {
+        ScoringRules::Japanese => "Japanese",
+        ScoringRules::Chinese => "Chinese",
+    };
+    writeln!(writer, "Rules: {rules}")?;
+    writeln!(writer, "Black: {}", score.black)?;
+    writeln!(writer, "White: {}", score.white)?;
+    writeln!(writer, "Result: {}", score.result())?;
+    if let Some(result) = goban.result() {
+        writeln!(writer, "Recorded result: {result}")?;
+    }
+    Ok(())
+}
+
+/// Game information from a game's root properties, as printed by `query --info`.
+#[derive(Debug, serde::Serialize)]
+struct GameInfoSummary {
+    game: usize,
+    #[serde(flatten)]
+    game_info: GameInfo,
+    result: Option<String>,
+    komi: Option<f64>,
+    handicap: Option<i64>,
+    rules: Option<String>,
+    size: (u8, u8),
+    /// Number of moves in the main line, including passes.
+    moves: usize,
+}
+
+fn game_info_summaries(collection: &[GameTree]) -> Result<Vec<GameInfoSummary>, QueryError> {
+    let roots: Vec<NodeDescription> = (0..collection.len() as u64)
+        .map(|game_number| NodeDescription {
+            game_number,
+            variation: 0,
+            node_number: NodeNumber::Number(0),
+        })
+        .collect();
+    let gobans = Goban::from_collection(collection, &roots, &GobanOptions::default())?;
+    let mut summaries = vec![];
+    for (game, goban) in gobans.into_iter().enumerate() {
+        let root = game_root(collection, game as u64)?;
+        let mut handicap = None;
+        let mut rules = None;
+        for prop in root.properties() {
+            match prop {
+                Prop::HA(n) => handicap = Some(*n),
+                Prop::RU(name) => rules = Some(name.to_string()),
+                _ => {}
+            }
+        }
+        let moves = std::iter::successors(Some(root), |node| node.children().next())
+            .filter(|node| {
+                node.properties()
+                    .any(|prop| matches!(prop, Prop::B(_) | Prop::W(_)))
+            })
+            .count();
+        summaries.push(GameInfoSummary {
+            game,
+            game_info: goban.game_info().clone(),
+            result: goban.result().map(str::to_string),
+            komi: goban.komi(),
+            handicap,
+            rules,
+            size: goban.size(),
+            moves,
+        });
+    }
+    Ok(summaries)
+}
+
+fn write_info_text(collection: &[GameTree], mut writer: impl Write) -> Result<(), QueryError> {
+    let summaries = game_info_summaries(collection)?;
+    for info in &summaries {
+        writeln!(writer, "Game #{}", info.game)?;
+        let player = |name: &Option<String>, rank: &Option<String>| match (name, rank) {
+            (Some(name), Some(rank)) => Some(format!("{name} {rank}")),
+            (Some(name), None) => Some(name.to_string()),
+            (None, Some(rank)) => Some(rank.to_string()),
+            (None, None) => None,
+        };
+        let game_info = &info.game_info;
+        let fields = [
+            (
+                "Black",
+                player(&game_info.black_player, &game_info.black_rank),
+            ),
+            (
+                "White",
+                player(&game_info.white_player, &game_info.white_rank),
+            ),
+            ("Result", info.result.clone()),
+            ("Komi", info.komi.map(|komi| komi.to_string())),
+            (
+                "Handicap",
+                info.handicap.map(|handicap| handicap.to_string()),
+            ),
+            ("Rules", info.rules.clone()),
+            ("Date", game_info.date.clone()),
+            ("Event", game_info.event.clone()),
+            ("Size", Some(format!("{}x{}", info.size.0, info.size.1))),
+            ("Moves", Some(info.moves.to_string())),
+        ];
+        for (label, value) in fields {
+            if let Some(value) = value {
+                writeln!(writer, "{label}: {value}")?;
+            }
+        }
+        if info.game < summaries.len() - 1 {
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_info_json(collection: &[GameTree], mut writer: impl Write) -> Result<(), QueryError> {
+    let games = game_info_summaries(collection)?;
+    serde_json::to_writer_pretty(&mut writer, &games).map_err(std::io::Error::from)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// A game's variations, as printed by `query --json`.
+#[derive(Debug, serde::Serialize)]
+struct GameSummary {
+    game: usize,
+    variations: Vec<VariationSummary>,
+}
+
+/// A variation's own nodes, from where it branches off its parent to its last node.
+#[derive(Debug, serde::Serialize)]
+struct VariationSummary {
+    variation: u64,
+    parent: Option<u64>,
+    start: u64,
+    end: u64,
+    first_move: Option<MoveSummary>,
+    names: Vec<NodeName>,
+    comments: Vec<u64>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct MoveSummary {
+    node: u64,
+    color: &'static str,
+    /// SGF coordinates of the move, or `None` for a pass.
+    point: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct NodeName {
+    node: u64,
+    name: String,
+}
+
+fn write_query_json(
+    collection: &[SgfNode<Prop>],
+    mut writer: impl Write,
+) -> Result<(), QueryError> {
+    let games = collection
+        .iter()
+        .enumerate()
+        .map(|(game, sgf_node)| {
+            Ok(GameSummary {
+                game,
+                variations: variation_summaries(sgf_node)?,
+            })
+        })
+        .collect::<Result<Vec<_>, QueryError>>()?;
+    serde_json::to_writer_pretty(&mut writer, &games).map_err(std::io::Error::from)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn variation_summaries(root: &SgfNode<Prop>) -> Result<Vec<VariationSummary>, QueryError> {
+    let size = get_board_size(root)?;
+    let mut variations: BTreeMap<u64, VariationSummary> = BTreeMap::new();
+    // Each variation's nodes are visited in order.
+    for node in SgfTraversal::new(root) {
+        let node_number = node.variation_node_number;
+        let summary = variations
+            .entry(node.variation)
+            .or_insert_with(|| VariationSummary {
+                variation: node.variation,
+                parent: (node.variation != 0).then_some(node.parent_variation),
+                start: node_number,
+                end: node_number,
+                first_move: None,
+                names: vec![],
+                comments: vec![],
+            });
+        summary.end = node_number;
+        for prop in node.sgf_node.properties() {
+            match prop {
+                Prop::B(mv) | Prop::W(mv) if summary.first_move.is_none() => {
+                    summary.first_move = Some(MoveSummary {
+                        node: node_number,
+                        color: if matches!(prop, Prop::B(_)) { "B" } else { "W" },
+                        point: move_point(mv, size),
+                    });
+                }
+                Prop::N(name) => summary.names.push(NodeName {
+                    node: node_number,
+                    name: name.to_string(),
+                }),
+                Prop::C(_) => summary.comments.push(node_number),
+                _ => {}
+            }
+        }
+    }
+    Ok(variations.into_values().collect())
+}
+
+fn move_point(mv: &Move, size: (u8, u8)) -> Option<String> {
+    match mv {
+        Move::Move(point) if !is_tt_pass(*point, size) => Some(format!(
+            "{}{}",
+            sgf_coordinate(point.x),
+            sgf_coordinate(point.y)
+        )),
+        _ => None,
+    }
+}
+
 fn write_query_text(
     collection: &[SgfNode<Prop>],
     mut writer: impl Write,
@@ -120,8 +379,10 @@ mod tests {
     use sgf_parse::{go::Prop, SgfNode};
 
     use super::{
-        query_game_index, query_node_index, query_variation_index, write_query_text, QueryError,
+        query_game_index, query_node_index, query_variation_index, write_info_json,
+        write_info_text, write_query_json, write_query_text, write_score, QueryError,
     };
+    use crate::QueryArgs;
 
     static TEST_DATA: &str = "\
 (;GM[1]FF[4]
@@ -196,6 +457,64 @@ v0, 0-3
         assert_eq!(output, expected);
     }
 
+    #[test]
+    fn query_json() {
+        let mut output = vec![];
+        write_query_json(&get_collection(), &mut output).unwrap();
+        let games: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(games.as_array().unwrap().len(), 2);
+        let variations = games[0]["variations"].as_array().unwrap();
+        assert_eq!(variations.len(), 9);
+        assert_eq!(
+            variations[6],
+            serde_json::json!({
+                "variation": 6,
+                "parent": 5,
+                "start": 7,
+                "end": 8,
+                "first_move": {"node": 7, "color": "B", "point": "fg"},
+                "names": [],
+                "comments": [],
+            })
+        );
+        assert_eq!(variations[0]["parent"], serde_json::Value::Null);
+        assert_eq!(variations[0]["first_move"]["point"], "ee");
+        assert_eq!(games[1]["variations"][1]["start"], 3);
+    }
+
+    #[test]
+    fn info() {
+        let sgf = "(;SZ[13]PB[Honinbo Shusaku]BR[7d]PW[Gennan Inseki]RE[B+2]HA[2]RU[Japanese]\
+                   ;B[dd];W[]C[pass](;B[jj])(;B[kk];W[jk]))\
+                   (;GM[1])";
+        let collection = sgf_parse::parse(sgf).unwrap();
+        let mut output = vec![];
+        write_info_text(&collection, &mut output).unwrap();
+        let expected = "\
+Game #0
+Black: Honinbo Shusaku 7d
+White: Gennan Inseki
+Result: B+2
+Handicap: 2
+Rules: Japanese
+Size: 13x13
+Moves: 3
+
+Game #1
+Size: 19x19
+Moves: 0
+";
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+        let mut output = vec![];
+        write_info_json(&collection, &mut output).unwrap();
+        let games: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(games[0]["black_rank"], "7d");
+        assert_eq!(games[0]["komi"], serde_json::Value::Null);
+        assert_eq!(games[0]["size"], serde_json::json!([13, 13]));
+        assert_eq!(games[1]["moves"], 0);
+    }
+
     #[test]
     fn game_index() {
         let result = query_game_index(&get_collection()).unwrap();
@@ -240,4 +559,22 @@ v0, 0-3
         let result = query_node_index(&get_collection(), 0, 9);
         assert!(matches!(result, Err(QueryError::VariationNotFound)));
     }
+
+    #[test]
+    fn score() {
+        let sgf = "(;SZ[5]KM[0.5]RE[B+4.5]AB[ca][cb][cc][cd][ce][ed]AW[da][db][dc][dd][de];W[bb])";
+        let query_args: QueryArgs =
+            clap::Parser::parse_from(["query", "--score", "--dead-stones", "bb,ed"]);
+        let mut output = vec![];
+        write_score(&sgf_parse::parse(sgf).unwrap(), &query_args, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let expected = "\
+Rules: Japanese
+Black: 11
+White: 6.5
+Result: B+4.5
+Recorded result: B+4.5
+";
+        assert_eq!(output, expected);
+    }
 }
@@ -0,0 +1,232 @@
+use std::ops::Range;
+
+use minidom::Element;
+use sgf_parse::GameTree;
+
+use super::svg::{self, prefix_ids, NAMESPACE};
+use super::{GobanRange, NodeDescription, NodeNumber, RenderOptions};
+
+use crate::errors::GobanError;
+use crate::goban::{game_root, Goban, GobanOptions};
+use crate::sgf_traversal::variation_nodes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationOptions {
+    /// Delay between frames in milliseconds.
+    pub frame_delay: u16,
+    /// First move to animate. The position before it is the first frame.
+    pub first_move: u64,
+    /// Last move to animate (or the end of the variation if `None`).
+    pub last_move: Option<u64>,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        AnimationOptions {
+            frame_delay: 1000,
+            first_move: 1,
+            last_move: None,
+        }
+    }
+}
+
+/// Returns a goban for each node of the selected variation in the animated move range.
+pub fn frames(
+    collection: &[GameTree],
+    node_description: &NodeDescription,
+    animation_options: &AnimationOptions,
+    goban_options: &GobanOptions,
+) -> Result<Vec<Goban>, GobanError> {
+    let root_node = game_root(collection, node_description.game_number)?;
+    let node_count = variation_nodes(root_node, node_description.variation)?.count() as u64;
+    let node_descriptions: Vec<NodeDescription> = (0..node_count)
+        .map(|n| NodeDescription {
+            node_number: NodeNumber::Number(n),
+            ..*node_description
+        })
+        .collect();
+    let frames: Vec<Goban> = Goban::from_collection(collection, &node_descriptions, goban_options)?
+        .into_iter()
+        .filter(|goban| {
+            let last_move = goban.moves().last().map(|(n, _)| n).unwrap_or(0);
+            last_move + 1 >= animation_options.first_move
+                && animation_options
+                    .last_move
+                    .map(|end| last_move <= end)
+                    .unwrap_or(true)
+        })
+        .collect();
+    if frames.is_empty() {
+        return Err(GobanError::InsufficientSgfNodes);
+    }
+    Ok(frames)
+}
+
+/// Returns options which draw the same part of the board in every frame.
+pub fn frame_options(
+    frames: &[Goban],
+    options: &RenderOptions,
+) -> Result<RenderOptions, GobanError> {
+    if !matches!(options.goban_range, GobanRange::ShrinkWrap) {
+        return Ok(options.clone());
+    }
+    let mut ranges: Option<(Range<u8>, Range<u8>)> = None;
+    for frame in frames {
+        let transformed = frame.transformed(&options.transform);
+        let (x_range, y_range) = options.goban_range.get_ranges(&transformed, options)?;
+        ranges = Some(match ranges {
+            None => (x_range, y_range),
+            Some((x, y)) => (
+                x_range.start.min(x.start)..x_range.end.max(x.end),
+                y_range.start.min(y.start)..y_range.end.max(y.end),
+            ),
+        });
+    }
+    Ok(match ranges {
+        Some((x_range, y_range)) => RenderOptions {
+            goban_range: GobanRange::Ranged(x_range, y_range),
+            ..options.clone()
+        },
+        None => options.clone(),
+    })
+}
+
+/// Renders the frames as an SVG which shows each in turn using SMIL animation.
+///
+/// Viewers without SMIL support show the first frame.
+pub fn render(
+    frames: &[Goban],
+    options: &RenderOptions,
+    animation_options: &AnimationOptions,
+) -> Result<Element, GobanError> {
+    let options = frame_options(frames, options)?;
+    let frame_count = frames.len();
+    let duration = frame_count as u64 * animation_options.frame_delay as u64;
+    let mut height: f64 = 0.0;
+    let mut groups = vec![];
+    for (i, frame) in frames.iter().enumerate() {
+        let mut frame_svg = svg::render(frame, &options)?;
+        let frame_height = viewbox_height(&frame_svg);
+        height = height.max(frame_height);
+        prefix_ids(&mut frame_svg, &format!("frame{i}-"));
+        frame_svg.set_attr("height", frame_height.to_string());
+
+        let visibility = if i == 0 { "visible" } else { "hidden" };
+        let mut group = Element::builder("g", NAMESPACE).attr("visibility", visibility);
+        if frame_count > 1 {
+            let start = i as f64 / frame_count as f64;
+            let end = (i + 1) as f64 / frame_count as f64;
+            let (values, key_times) = if i == 0 {
+                ("visible;hidden".to_string(), format!("0;{end}"))
+            } else if i + 1 == frame_count {
+                ("hidden;visible".to_string(), format!("0;{start}"))
+            } else {
+                (
+                    "hidden;visible;hidden".to_string(),
+                    format!("0;{start};{end}"),
+                )
+            };
+            group = group.append(
+                Element::builder("animate", NAMESPACE)
+                    .attr("attributeName", "visibility")
+                    .attr("values", values)
+                    .attr("keyTimes", key_times)
+                    .attr("calcMode", "discrete")
+                    .attr("dur", format!("{duration}ms"))
+                    .attr("repeatCount", "indefinite")
+                    .build(),
+            );
+        }
+        groups.push(group.append(frame_svg).build());
+    }
+
+    let svg = Element::builder("svg", NAMESPACE)
+        .attr(
+            "viewBox",
+            format!("0 0 {} {}", options.viewbox_width, height),
+        )
+        .attr("width", options.viewbox_width.to_string())
+        .append_all(groups)
+        .build();
+    Ok(svg)
+}
+
+/// Returns the height from a rendered svg's viewBox.
+fn viewbox_height(svg: &Element) -> f64 {
+    svg.attr("viewBox")
+        .and_then(|viewbox| viewbox.split(' ').nth(3))
+        .and_then(|height| height.parse().ok())
+        .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{GobanRange, NodeDescription, RenderOptions};
+
+    use super::{frame_options, frames, render, AnimationOptions};
+
+    static TEST_DATA: &str = "(;SZ[9]AB[ee];W[de];B[cc];W[dd];B[cd])";
+
+    #[test]
+    fn move_range() {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        let animation_options = AnimationOptions {
+            first_move: 2,
+            last_move: Some(3),
+            ..Default::default()
+        };
+        let frames = frames(
+            &collection,
+            &NodeDescription::default(),
+            &animation_options,
+            &Default::default(),
+        )
+        .unwrap();
+        let move_counts: Vec<usize> = frames.iter().map(|frame| frame.moves().count()).collect();
+        assert_eq!(move_counts, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn shrink_wrap_covers_all_frames() {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        let frames = frames(
+            &collection,
+            &NodeDescription::default(),
+            &AnimationOptions::default(),
+            &Default::default(),
+        )
+        .unwrap();
+        let options = RenderOptions {
+            goban_range: GobanRange::ShrinkWrap,
+            ..Default::default()
+        };
+        let first_frame = &frames[..1];
+        let first_options = frame_options(first_frame, &options).unwrap();
+        let all_options = frame_options(&frames, &options).unwrap();
+        assert_eq!(first_options.goban_range, GobanRange::Ranged(3..6, 3..6));
+        assert_eq!(all_options.goban_range, GobanRange::Ranged(0..6, 0..6));
+    }
+
+    #[test]
+    fn animated_frames() {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        let animation_options = AnimationOptions {
+            frame_delay: 500,
+            ..Default::default()
+        };
+        let frames = frames(
+            &collection,
+            &NodeDescription::default(),
+            &animation_options,
+            &Default::default(),
+        )
+        .unwrap();
+        let svg = render(&frames, &RenderOptions::default(), &animation_options).unwrap();
+        let groups: Vec<_> = svg.children().collect();
+        assert_eq!(groups.len(), 5);
+        let animate = groups[2].get_child("animate", super::NAMESPACE).unwrap();
+        assert_eq!(animate.attr("values"), Some("hidden;visible;hidden"));
+        assert_eq!(animate.attr("keyTimes"), Some("0;0.4;0.6"));
+        assert_eq!(animate.attr("dur"), Some("2500ms"));
+    }
+}
@@ -5,7 +5,7 @@ use crate::errors::{GobanError, UsageError};
 use crate::goban::Goban;
 use crate::RenderOptions;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum GobanRange {
     ShrinkWrap,
     #[default]
@@ -48,6 +48,12 @@ impl GobanRange {
                 if options.draw_arrows {
                     points.extend(goban.arrows().flat_map(|(p1, p2)| vec![p1, p2]))
                 }
+                if options.draw_territory {
+                    points.extend(goban.territory().map(|(p, _)| p))
+                }
+                if options.variation_markup.is_some() {
+                    points.extend(goban.next_moves().map(|(_, s)| (s.x, s.y)))
+                }
                 // Don't necessarily include dimmed points!
                 let x_start = {
                     let p = points
@@ -1,7 +1,9 @@
+use std::collections::HashMap;
+
 use minidom::Element;
 
 use crate::errors::GobanError;
-use crate::goban::StoneColor;
+use crate::goban::{Annotation, StoneColor};
 use crate::render::generated_styles::GeneratedStyle;
 use crate::render::svg::NAMESPACE;
 
@@ -23,6 +25,23 @@ pub struct GobanStyle {
     black_stone_selected_color: String,
     white_stone_selected_color: String,
     empty_selected_color: String,
+    black_territory_color: Option<String>,
+    white_territory_color: Option<String>,
+    territory_size: Option<f64>,
+    dead_stone_opacity: Option<f64>,
+    ghost_stone_opacity: Option<f64>,
+    black_ownership_color: Option<String>,
+    white_ownership_color: Option<String>,
+    ownership_size: Option<f64>,
+    ownership_opacity: Option<f64>,
+    best_candidate_color: Option<String>,
+    candidate_color: Option<String>,
+    annotation_color: Option<String>,
+    annotation_symbols: Option<HashMap<Annotation, String>>,
+    last_move_marker: Option<LastMoveMarker>,
+    caption_font_family: Option<String>,
+    caption_font_size: Option<f64>,
+    caption_color: Option<String>,
     defs: Option<String>,
 }
 
@@ -81,6 +100,79 @@ impl GobanStyle {
         }
     }
 
+    pub fn territory_color(&self, color: StoneColor) -> &str {
+        match color {
+            StoneColor::Black => self.black_territory_color.as_deref().unwrap_or("black"),
+            StoneColor::White => self.white_territory_color.as_deref().unwrap_or("white"),
+        }
+    }
+
+    pub fn territory_size(&self) -> f64 {
+        self.territory_size.unwrap_or(0.3)
+    }
+
+    pub fn dead_stone_opacity(&self) -> f64 {
+        self.dead_stone_opacity.unwrap_or(0.5)
+    }
+
+    pub fn ownership_color(&self, color: StoneColor) -> &str {
+        match color {
+            StoneColor::Black => self.black_ownership_color.as_deref().unwrap_or("black"),
+            StoneColor::White => self.white_ownership_color.as_deref().unwrap_or("white"),
+        }
+    }
+
+    pub fn ownership_size(&self) -> f64 {
+        self.ownership_size.unwrap_or(0.5)
+    }
+
+    /// Opacity of ownership shading for a point owned with certainty.
+    pub fn ownership_opacity(&self) -> f64 {
+        self.ownership_opacity.unwrap_or(0.8)
+    }
+
+    /// Fill for the engine's preferred candidate move.
+    pub fn best_candidate_color(&self) -> &str {
+        self.best_candidate_color.as_deref().unwrap_or("#4fb3e8")
+    }
+
+    /// Fill for other candidate moves, faded by their share of the visits.
+    pub fn candidate_color(&self) -> &str {
+        self.candidate_color.as_deref().unwrap_or("#6cc16c")
+    }
+
+    pub fn ghost_stone_opacity(&self) -> f64 {
+        self.ghost_stone_opacity.unwrap_or(0.5)
+    }
+
+    pub fn annotation_color(&self) -> &str {
+        self.annotation_color.as_deref().unwrap_or("#c8322c")
+    }
+
+    pub fn annotation_symbol(&self, annotation: Annotation) -> &str {
+        self.annotation_symbols
+            .as_ref()
+            .and_then(|symbols| symbols.get(&annotation))
+            .map(String::as_str)
+            .unwrap_or(annotation.default_symbol())
+    }
+
+    pub fn last_move_marker(&self) -> LastMoveMarker {
+        self.last_move_marker.unwrap_or_default()
+    }
+
+    pub fn caption_font_family(&self) -> Option<&str> {
+        self.caption_font_family.as_deref()
+    }
+
+    pub fn caption_font_size(&self) -> f64 {
+        self.caption_font_size.unwrap_or(0.4)
+    }
+
+    pub fn caption_color(&self) -> &str {
+        self.caption_color.as_deref().unwrap_or(&self.label_color)
+    }
+
     pub fn defs(&self) -> Result<Vec<Element>, GobanError> {
         let linehead = Element::builder("marker", NAMESPACE)
             .attr("id", "linehead")
@@ -122,6 +214,15 @@ impl GobanStyle {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LastMoveMarker {
+    #[default]
+    Circle,
+    Dot,
+    Triangle,
+}
+
 impl Default for GobanStyle {
     fn default() -> Self {
         GeneratedStyle::Simple.style().clone()
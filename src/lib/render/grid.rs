@@ -0,0 +1,225 @@
+use minidom::Element;
+
+use super::animation::frame_options;
+use super::svg::{self, prefix_ids, NAMESPACE};
+use super::{NodeDescription, RenderOptions};
+
+use crate::errors::GobanError;
+use crate::goban::Goban;
+
+// Text sizes as fractions of the diagram width.
+static CAPTION_FONT_SIZE: f64 = 0.04;
+static TITLE_FONT_SIZE: f64 = 0.06;
+static LINE_HEIGHT: f64 = 1.25;
+
+static FONT_FAMILY: &str = "Inter";
+static FONT_WEIGHT: usize = 700;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridOptions {
+    /// Number of diagrams in each row.
+    pub columns: usize,
+    /// Space between diagrams, and around the edge of the sheet.
+    pub gap: f64,
+    /// Title to draw above the diagrams.
+    pub title: Option<String>,
+}
+
+impl Default for GridOptions {
+    fn default() -> Self {
+        GridOptions {
+            columns: 3,
+            gap: 40.0,
+            title: None,
+        }
+    }
+}
+
+/// Returns a caption identifying the node a diagram shows.
+pub fn caption(goban: &Goban, node_description: &NodeDescription) -> String {
+    let mut parts = vec![];
+    if node_description.game_number != 0 {
+        parts.push(format!("game {}", node_description.game_number));
+    }
+    if node_description.variation != 0 {
+        parts.push(format!("variation {}", node_description.variation));
+    }
+    parts.push(match goban.moves().last() {
+        Some((n, _)) => format!("move {n}"),
+        None => "start".to_string(),
+    });
+    let caption = parts.join(", ");
+    caption[..1].to_uppercase() + &caption[1..]
+}
+
+/// Lays out the diagrams in rows, each with its caption above it, as a single SVG.
+///
+/// With shrink wrapping every diagram shows the same part of the board.
+pub fn render(
+    gobans: &[Goban],
+    captions: &[String],
+    options: &RenderOptions,
+    grid_options: &GridOptions,
+) -> Result<Element, GobanError> {
+    let options = frame_options(gobans, options)?;
+    let cell_width = options.viewbox_width;
+    let gap = grid_options.gap;
+    let caption_font_size = cell_width * CAPTION_FONT_SIZE;
+    let title_font_size = cell_width * TITLE_FONT_SIZE;
+    let columns = grid_options.columns.min(gobans.len()).max(1);
+
+    let mut elements = vec![];
+    let mut y = gap;
+    if let Some(title) = &grid_options.title {
+        elements.push(text(
+            title,
+            gap,
+            y + title_font_size,
+            title_font_size,
+            &options,
+        ));
+        y += title_font_size * LINE_HEIGHT + gap;
+    }
+    for (row, row_gobans) in gobans.chunks(columns).enumerate() {
+        let mut row_height: f64 = 0.0;
+        for (column, goban) in row_gobans.iter().enumerate() {
+            let i = row * columns + column;
+            let x = gap + column as f64 * (cell_width + gap);
+            let mut diagram_y = y;
+            if let Some(caption) = captions.get(i).filter(|caption| !caption.is_empty()) {
+                elements.push(text(
+                    caption,
+                    x,
+                    y + caption_font_size,
+                    caption_font_size,
+                    &options,
+                ));
+                diagram_y += caption_font_size * LINE_HEIGHT;
+            }
+            let mut diagram = svg::render(goban, &options)?;
+            let height = viewbox_height(&diagram);
+            prefix_ids(&mut diagram, &format!("cell{i}-"));
+            diagram.set_attr("x", x.to_string());
+            diagram.set_attr("y", diagram_y.to_string());
+            diagram.set_attr("height", height.to_string());
+            elements.push(diagram);
+            row_height = row_height.max(diagram_y - y + height);
+        }
+        y += row_height + gap;
+    }
+
+    let width = gap + columns as f64 * (cell_width + gap);
+    let svg = Element::builder("svg", NAMESPACE)
+        .attr("viewBox", format!("0 0 {width} {y}"))
+        .attr("width", width.to_string())
+        .attr("font-family", FONT_FAMILY)
+        .attr("font-weight", FONT_WEIGHT)
+        .append(
+            Element::builder("rect", NAMESPACE)
+                .attr("fill", "white")
+                .attr("height", "100%")
+                .attr("width", "100%")
+                .build(),
+        )
+        .append_all(elements)
+        .build();
+    Ok(svg)
+}
+
+fn text(content: &str, x: f64, y: f64, font_size: f64, options: &RenderOptions) -> Element {
+    let mut builder = Element::builder("text", NAMESPACE)
+        .attr("x", x.to_string())
+        .attr("y", y.to_string())
+        .attr("font-size", font_size.to_string())
+        .attr("fill", options.style.caption_color());
+    if let Some(font_family) = options.style.caption_font_family() {
+        builder = builder.attr("font-family", font_family);
+    }
+    builder.append(content).build()
+}
+
+/// Returns the height from a rendered svg's viewBox.
+fn viewbox_height(svg: &Element) -> f64 {
+    svg.attr("viewBox")
+        .and_then(|viewbox| viewbox.split(' ').nth(3))
+        .and_then(|height| height.parse().ok())
+        .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{NodeDescription, NodeNumber, RenderOptions};
+    use crate::Goban;
+
+    use super::{caption, render, GridOptions, NAMESPACE};
+
+    static TEST_DATA: &str = "(;SZ[9];B[ee];W[dd];B[cc](;W[cd])(;W[dc]))";
+
+    #[test]
+    fn captions() {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        let node_descriptions = vec![
+            NodeDescription {
+                game_number: 0,
+                variation: 0,
+                node_number: NodeNumber::Number(0),
+            },
+            NodeDescription {
+                game_number: 0,
+                variation: 1,
+                node_number: NodeNumber::Last,
+            },
+        ];
+        let gobans =
+            Goban::from_collection(&collection, &node_descriptions, &Default::default()).unwrap();
+        assert_eq!(caption(&gobans[0], &node_descriptions[0]), "Start");
+        assert_eq!(
+            caption(&gobans[1], &node_descriptions[1]),
+            "Variation 1, move 4"
+        );
+    }
+
+    #[test]
+    fn layout() {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        let node_descriptions: Vec<NodeDescription> = (0..5)
+            .map(|n| NodeDescription {
+                node_number: NodeNumber::Number(n),
+                ..Default::default()
+            })
+            .collect();
+        let gobans =
+            Goban::from_collection(&collection, &node_descriptions, &Default::default()).unwrap();
+        let captions: Vec<String> = (0..5).map(|n| n.to_string()).collect();
+        let options = RenderOptions {
+            viewbox_width: 100.0,
+            ..Default::default()
+        };
+        let grid_options = GridOptions {
+            columns: 2,
+            gap: 10.0,
+            title: Some("Opening".to_string()),
+        };
+        let svg = render(&gobans, &captions, &options, &grid_options).unwrap();
+        assert_eq!(svg.attr("width"), Some("230"));
+        let diagrams: Vec<_> = svg.children().filter(|e| e.is("svg", NAMESPACE)).collect();
+        assert_eq!(diagrams.len(), 5);
+        let positions: Vec<_> = diagrams
+            .iter()
+            .map(|diagram| diagram.attr("x").unwrap())
+            .collect();
+        assert_eq!(positions, vec!["10", "120", "10", "120", "10"]);
+        assert_eq!(diagrams[0].attr("y"), diagrams[1].attr("y"));
+        assert_ne!(diagrams[0].attr("y"), diagrams[2].attr("y"));
+        let texts: Vec<String> = svg
+            .children()
+            .filter(|e| e.is("text", NAMESPACE))
+            .map(|e| e.text())
+            .collect();
+        assert_eq!(texts, vec!["Opening", "0", "1", "2", "3", "4"]);
+        let clip_path = diagrams[4]
+            .get_child("defs", NAMESPACE)
+            .and_then(|defs| defs.get_child("clipPath", NAMESPACE));
+        assert_eq!(clip_path.unwrap().attr("id"), Some("cell4-board-clip"));
+    }
+}
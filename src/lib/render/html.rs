@@ -0,0 +1,133 @@
+use super::animation::frame_options;
+use super::svg::{self, prefix_ids};
+use super::RenderOptions;
+
+use crate::errors::GobanError;
+use crate::goban::Goban;
+
+static STYLE: &str = "\
+body { font-family: sans-serif; max-width: 40em; margin: 1em auto; padding: 0 1em; }
+.frame svg { display: block; width: 100%; height: auto; }
+nav { display: flex; align-items: center; gap: 0.5em; margin: 0.5em 0; }
+#label { flex: 1; text-align: center; }
+.comment { white-space: pre-wrap; }";
+
+static SCRIPT: &str = "\
+const frames = document.querySelectorAll('.frame');
+let current = 0;
+function show(n) {
+  current = Math.max(0, Math.min(frames.length - 1, n));
+  frames.forEach((frame, i) => { frame.hidden = i !== current; });
+  document.getElementById('label').textContent = frames[current].dataset.label;
+}
+document.getElementById('first').onclick = () => show(0);
+document.getElementById('prev').onclick = () => show(current - 1);
+document.getElementById('next').onclick = () => show(current + 1);
+document.getElementById('last').onclick = () => show(frames.length - 1);
+document.addEventListener('keydown', (event) => {
+  if (event.key === 'ArrowLeft') show(current - 1);
+  if (event.key === 'ArrowRight') show(current + 1);
+  if (event.key === 'Home') show(0);
+  if (event.key === 'End') show(frames.length - 1);
+});
+show(0);";
+
+/// Renders the frames as a standalone HTML page for stepping through them with their comments.
+pub fn render(frames: &[Goban], options: &RenderOptions) -> Result<String, GobanError> {
+    // Comments are shown as text in the page rather than drawn on the board.
+    let options = RenderOptions {
+        draw_comment: false,
+        ..frame_options(frames, options)?
+    };
+    let mut body = String::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let mut frame_svg = svg::render(frame, &options)?;
+        prefix_ids(&mut frame_svg, &format!("frame{i}-"));
+        let mut buffer: Vec<u8> = vec![];
+        // Writing to a Vec can't fail.
+        frame_svg.write_to(&mut buffer).unwrap();
+        let label = match frame.moves().last() {
+            Some((n, _)) => format!("Move {n}"),
+            None => "Start".to_string(),
+        };
+        body.push_str(&format!(
+            "<div class=\"frame\" data-label=\"{}\">\n{}\n",
+            escape(&label),
+            String::from_utf8_lossy(&buffer)
+        ));
+        if let Some(comment) = frame.comment() {
+            body.push_str(&format!("<p class=\"comment\">{}</p>\n", escape(comment)));
+        }
+        body.push_str("</div>\n");
+    }
+
+    let title = match frames.first().map(Goban::game_info) {
+        Some(info) if info.black_player.is_some() || info.white_player.is_some() => format!(
+            "{} vs {}",
+            info.black_player.as_deref().unwrap_or("Black"),
+            info.white_player.as_deref().unwrap_or("White")
+        ),
+        _ => "Go game".to_string(),
+    };
+    Ok(format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>{}</title>
+<style>
+{STYLE}
+</style>
+</head>
+<body>
+<nav>
+<button id=\"first\">&#x23EE;</button>
+<button id=\"prev\">&#x25C0;</button>
+<span id=\"label\"></span>
+<button id=\"next\">&#x25B6;</button>
+<button id=\"last\">&#x23ED;</button>
+</nav>
+{body}<script>
+{SCRIPT}
+</script>
+</body>
+</html>",
+        escape(&title)
+    ))
+}
+
+/// Escapes text for use in HTML content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::animation::{frames, AnimationOptions};
+    use crate::render::{NodeDescription, RenderOptions};
+
+    use super::render;
+
+    #[test]
+    fn frames_and_comments() {
+        let sgf = "(;SZ[9]PB[Honinbo <Shusaku>];B[ee]C[Tengen & more];W[dd])";
+        let collection = sgf_parse::parse(sgf).unwrap();
+        let frames = frames(
+            &collection,
+            &NodeDescription::default(),
+            &AnimationOptions::default(),
+            &Default::default(),
+        )
+        .unwrap();
+        let html = render(&frames, &RenderOptions::default()).unwrap();
+        assert_eq!(html.matches("<div class=\"frame\"").count(), 3);
+        assert!(html.contains("data-label=\"Start\""));
+        assert!(html.contains("data-label=\"Move 2\""));
+        assert!(html.contains("<p class=\"comment\">Tengen &amp; more</p>"));
+        assert!(html.contains("<title>Honinbo &lt;Shusaku&gt; vs White</title>"));
+        assert!(html.contains("id=\"frame1-board-clip\""));
+    }
+}
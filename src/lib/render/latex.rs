@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use super::{board_label_text, diagram_stones, get_move_numbers, repeated_moves, RenderOptions};
+
+use crate::errors::GobanError;
+use crate::goban::{Goban, StoneColor};
+
+/// Renders the goban as a `psgo` board environment for inclusion in LaTeX documents.
+///
+/// The psgo package only draws square boards, so non-square boards are drawn as a partial board.
+/// Coordinates are drawn on all sides if any label sides are selected.
+pub fn render(goban: &Goban, options: &RenderOptions) -> Result<String, GobanError> {
+    let transformed;
+    let goban = if options.transform.is_identity() {
+        goban
+    } else {
+        transformed = goban.transformed(&options.transform);
+        &transformed
+    };
+    let (x_range, y_range) = options.goban_range.get_ranges(goban, options)?;
+    let (board_width, board_height) = goban.size();
+    if board_width > 25 {
+        // psgo columns are lettered a to z, skipping i.
+        return Err(GobanError::UnlabellableRange);
+    }
+    let in_range = |&(x, y): &(u8, u8)| x_range.contains(&x) && y_range.contains(&y);
+    let position = |(x, y): (u8, u8)| {
+        format!(
+            "{{{}}}{{{}}}",
+            board_label_text(x).to_lowercase(),
+            board_height - y
+        )
+    };
+
+    let stones: HashMap<(u8, u8), StoneColor> = diagram_stones(goban, options)
+        .into_iter()
+        .map(|stone| ((stone.x, stone.y), stone.color))
+        .filter(|(point, _)| in_range(point))
+        .collect();
+    // The optional argument drawn on each point: a move number, mark or label.
+    let mut annotations: HashMap<(u8, u8), String> = HashMap::new();
+    if let Some(move_number_options) = options.move_number_options {
+        for (n, stone) in get_move_numbers(goban, options) {
+            let n = n + move_number_options.count_from - move_number_options.start;
+            annotations.insert((stone.x, stone.y), n.to_string());
+        }
+    }
+    let markup = [
+        (
+            options.draw_marks,
+            goban.marks().collect::<Vec<_>>(),
+            "\\markma",
+        ),
+        (
+            options.draw_triangles,
+            goban.triangles().collect(),
+            "\\marktr",
+        ),
+        (options.draw_circles, goban.circles().collect(), "\\markcr"),
+        (options.draw_squares, goban.squares().collect(), "\\marksq"),
+    ];
+    for (draw, points, mark) in markup.iter() {
+        if *draw {
+            for point in points {
+                annotations
+                    .entry(*point)
+                    .or_insert_with(|| mark.to_string());
+            }
+        }
+    }
+    if options.draw_labels {
+        for (point, text) in goban.labels() {
+            annotations
+                .entry(*point)
+                .or_insert_with(|| format!("\\marklb{{{}}}", escape(text)));
+        }
+    }
+
+    let star = if options.label_sides.is_empty() {
+        "*"
+    } else {
+        ""
+    };
+    let board_size = board_width.max(board_height);
+    let partial = x_range.len() != board_size as usize || y_range.len() != board_size as usize;
+    let environment = if partial {
+        format!("psgopartialboard{star}")
+    } else {
+        format!("psgoboard{star}")
+    };
+    let mut lines = vec!["% Requires \\usepackage{psgo}".to_string()];
+    if partial {
+        lines.push(format!(
+            "\\begin{{{environment}}}[{board_size}]{{({},{})({},{})}}",
+            x_range.start + 1,
+            board_height - y_range.end + 1,
+            x_range.end,
+            board_height - y_range.start,
+        ));
+    } else {
+        lines.push(format!("\\begin{{{environment}}}[{board_size}]"));
+    }
+
+    let mut points: Vec<(u8, u8)> = stones.keys().copied().collect();
+    points.extend(
+        annotations
+            .keys()
+            .filter(|point| in_range(point) && !stones.contains_key(point)),
+    );
+    points.sort_unstable_by_key(|&(x, y)| (y, x));
+    for point in points {
+        let annotation = annotations.get(&point);
+        match stones.get(&point) {
+            Some(color) => {
+                let color = match color {
+                    StoneColor::Black => "black",
+                    StoneColor::White => "white",
+                };
+                let annotation = annotation.map(|a| format!("[{a}]")).unwrap_or_default();
+                lines.push(format!("\\stone{annotation}{{{color}}}{}", position(point)));
+            }
+            None => {
+                // Move numbers are only drawn on stones.
+                if let Some(annotation) = annotation.filter(|a| a.starts_with('\\')) {
+                    lines.push(format!("\\markpos{{{annotation}}}{}", position(point)));
+                }
+            }
+        }
+    }
+    lines.push(format!("\\end{{{environment}}}"));
+
+    if options.kifu_mode {
+        let repeated_moves: Vec<String> = repeated_moves(goban, options)
+            .iter()
+            .map(|(move_num, original)| format!("{move_num} at {original}"))
+            .collect();
+        if !repeated_moves.is_empty() {
+            lines.push(String::new());
+            lines.push(repeated_moves.join(", "));
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Escapes characters with special meaning in LaTeX.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{GobanRange, MoveNumberOptions};
+    use crate::{Goban, RenderOptions};
+
+    use super::{escape, render};
+
+    #[test]
+    fn full_board() {
+        let sgf = "(;SZ[9]AB[cc][dc]AW[cd][dd]TR[cc]CR[dd]LB[ee:A]SQ[ff])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let options = RenderOptions {
+            draw_triangles: true,
+            draw_circles: true,
+            draw_squares: true,
+            draw_labels: true,
+            ..Default::default()
+        };
+        let diagram = render(&goban, &options).unwrap();
+        let expected = "\
+% Requires \\usepackage{psgo}
+\\begin{psgoboard*}[9]
+\\stone[\\marktr]{black}{c}{7}
+\\stone{black}{d}{7}
+\\stone{white}{c}{6}
+\\stone[\\markcr]{white}{d}{6}
+\\markpos{\\marklb{A}}{e}{5}
+\\markpos{\\marksq}{f}{4}
+\\end{psgoboard*}";
+        assert_eq!(diagram, expected);
+    }
+
+    #[test]
+    fn move_numbers_in_range() {
+        let sgf = "(;SZ[19];B[pd];W[dd];B[pq];W[dp];B[fq];W[cn];B[jp];W[qo];B[qp];W[pn];B[cc])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let options = RenderOptions {
+            goban_range: GobanRange::Ranged(11..19, 12..19),
+            move_number_options: Some(MoveNumberOptions {
+                start: 8,
+                end: Some(10),
+                count_from: 1,
+            }),
+            ..Default::default()
+        };
+        let diagram = render(&goban, &options).unwrap();
+        let expected = "\
+% Requires \\usepackage{psgo}
+\\begin{psgopartialboard*}[19]{(12,1)(19,7)}
+\\stone[3]{white}{q}{6}
+\\stone[1]{white}{r}{5}
+\\stone[2]{black}{r}{4}
+\\stone{black}{q}{3}
+\\end{psgopartialboard*}";
+        assert_eq!(diagram, expected);
+    }
+
+    #[test]
+    fn escapes_labels() {
+        assert_eq!(escape("50% & #1"), "50\\% \\& \\#1");
+    }
+}
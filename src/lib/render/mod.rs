@@ -3,13 +3,27 @@ mod goban_range;
 mod goban_style;
 mod options;
 
+pub mod animation;
+pub mod grid;
+pub mod html;
+pub mod latex;
+pub mod overlay;
+pub mod sensei;
 pub mod svg;
 pub mod text;
 
 pub use generated_styles::GeneratedStyle;
 pub use goban_range::GobanRange;
-pub use goban_style::GobanStyle;
-pub use options::{BoardSideSet, MoveNumberOptions, NodeDescription, NodeNumber, RenderOptions};
+pub use goban_style::{GobanStyle, LastMoveMarker};
+pub use options::{
+    BoardSideSet, MoveNumberOptions, NodeDescription, NodeNumber, RenderOptions, Transform,
+    VariationMarkup,
+};
+
+use std::collections::HashMap;
+
+use crate::goban::{Annotation, Goban, Stone, StoneColor};
+use crate::scoring::{self, ScoringRules};
 
 fn board_label_text(x: u8) -> String {
     if x + b'A' < b'I' {
@@ -18,3 +32,428 @@ fn board_label_text(x: u8) -> String {
         ((x + b'B') as char).to_string() // skip 'I'
     }
 }
+
+/// Returns the stones to draw, sorted by position.
+///
+/// In kifu mode stones are never removed from the board, and numbered stones take precedence.
+fn diagram_stones(goban: &Goban, options: &RenderOptions) -> Vec<Stone> {
+    let mut stones: Vec<Stone> = if options.kifu_pages.is_some() {
+        // Draw the position before the page's first move, then the first stone played at each
+        // point during the page.
+        let start = options.move_number_options.unwrap().start;
+        let first_move = goban
+            .moves()
+            .map(|(n, _)| n)
+            .find(|&n| n >= start)
+            .unwrap_or(start);
+        let mut stones: HashMap<(u8, u8), Stone> = goban
+            .stones_before_move(first_move)
+            .map(|stone| ((stone.x, stone.y), stone))
+            .collect();
+        for (_, stone) in get_move_numbers(goban, options) {
+            stones.insert((stone.x, stone.y), stone);
+        }
+        stones.into_values().collect()
+    } else if options.kifu_mode {
+        // For each intersection draw the first numbered stone, or the last non-numbered stone.
+        let mut stones: HashMap<(u8, u8), Stone> = HashMap::new();
+        let mut numbered_stones: HashMap<(u8, u8), Stone> = HashMap::new();
+        for (n, stone) in goban.moves() {
+            if n >= options.move_number_options.unwrap().start {
+                numbered_stones.entry((stone.x, stone.y)).or_insert(stone);
+            }
+            stones.insert((stone.x, stone.y), stone);
+        }
+        for (key, stone) in numbered_stones {
+            stones.insert(key, stone);
+        }
+        stones.into_values().collect()
+    } else {
+        goban.stones().collect()
+    };
+    stones.sort_by_key(|stone| (stone.y, stone.x));
+    stones
+}
+
+/// Returns the numbered moves to draw, sorted by move number.
+fn get_move_numbers(goban: &Goban, options: &RenderOptions) -> Vec<(u64, Stone)> {
+    let move_number_options = match options.move_number_options {
+        Some(move_number_options) => move_number_options,
+        None => return Vec::new(),
+    };
+    let numbered_moves = goban
+        .moves()
+        .skip_while(|&(n, _)| n < move_number_options.start)
+        .take_while(|&(n, _)| move_number_options.end.map(|end| n <= end).unwrap_or(true));
+    let mut move_numbers: HashMap<(u8, u8), (u64, Stone)> = HashMap::new();
+    for (n, stone) in numbered_moves {
+        if options.kifu_mode {
+            // In Kifu mode we care about the first numbered stone played.
+            move_numbers.entry((stone.x, stone.y)).or_insert((n, stone));
+        } else {
+            // Otherwise we care about the last numbered stone played.
+            move_numbers.insert((stone.x, stone.y), (n, stone));
+        }
+    }
+    let mut move_numbers: Vec<(u64, Stone)> = move_numbers.values().copied().collect();
+    move_numbers.sort_unstable_by_key(|(n, _)| *n);
+    move_numbers
+}
+
+/// Returns the stones to mark with their move's annotations: the numbered moves if move numbers
+/// are drawn, and otherwise the last move.
+fn annotated_stones<'a>(
+    goban: &'a Goban,
+    options: &RenderOptions,
+) -> Vec<(Stone, &'a [Annotation])> {
+    let moves = if options.move_number_options.is_some() {
+        get_move_numbers(goban, options)
+    } else {
+        goban.moves().last().into_iter().collect()
+    };
+    moves
+        .into_iter()
+        .map(|(n, stone)| (stone, goban.annotations(n)))
+        .filter(|(_, annotations)| !annotations.is_empty())
+        .collect()
+}
+
+/// Returns `(move, original)` move numbers for each numbered move played on a point where an
+/// earlier numbered stone is shown, as listed below kifu diagrams.
+fn repeated_moves(goban: &Goban, options: &RenderOptions) -> Vec<(u64, u64)> {
+    let move_number_options = match options.move_number_options {
+        Some(move_number_options) => move_number_options,
+        None => return Vec::new(),
+    };
+    let mut repeated_moves = Vec::new();
+    let mut seen_moves: HashMap<(u8, u8), u64> = HashMap::new();
+    // Kifu pages only cover moves up to the end of the page.
+    let last_move = match options.kifu_pages {
+        Some(_) => move_number_options.end,
+        None => None,
+    };
+    let moves = goban
+        .moves()
+        .take_while(|&(n, _)| last_move.map(|end| n <= end).unwrap_or(true));
+    for (n, stone) in moves {
+        match seen_moves.entry((stone.x, stone.y)) {
+            std::collections::hash_map::Entry::Occupied(mut entry) => {
+                if *entry.get() < move_number_options.start {
+                    entry.insert(n);
+                }
+                let value = *entry.get();
+                let in_range = value >= move_number_options.start
+                    && move_number_options
+                        .end
+                        .map(|end| value <= end)
+                        .unwrap_or(true);
+                if n > value && in_range {
+                    repeated_moves.push((
+                        n + move_number_options.count_from - move_number_options.start,
+                        value + move_number_options.count_from - move_number_options.start,
+                    ));
+                }
+            }
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(n);
+            }
+        }
+    }
+    repeated_moves.sort_unstable();
+    repeated_moves
+}
+
+/// Returns `(id, text)` for each caption line to draw below the board.
+fn caption_lines(goban: &Goban, options: &RenderOptions) -> Vec<(&'static str, String)> {
+    let mut lines = vec![];
+    // Judgments of the position before the first move have no stone to be drawn next to.
+    if options.draw_annotations || options.annotation_footnotes {
+        if let Some(text) = position_judgment_text(goban, options) {
+            lines.push(("position-judgment", text));
+        }
+    }
+    if options.annotation_footnotes {
+        if let Some(text) = annotations_text(goban, options) {
+            lines.push(("annotations", text));
+        }
+    }
+    if options.draw_captures {
+        lines.push(("captures", captures_text(goban, options)));
+    }
+    if options.draw_score {
+        lines.push(("score", score_text(goban, options.scoring_rules)));
+        // Game info already includes the komi and result.
+        if !options.draw_game_info {
+            if let Some(text) = game_result_text(goban) {
+                lines.push(("result", text));
+            }
+        }
+    }
+    lines
+}
+
+/// Returns the lines of game information to draw above the board.
+fn game_info_lines(goban: &Goban) -> Vec<String> {
+    let info = goban.game_info();
+    let mut lines = vec![];
+    let event: Vec<&str> = [&info.event, &info.date]
+        .iter()
+        .filter_map(|s| s.as_deref())
+        .collect();
+    if !event.is_empty() {
+        lines.push(event.join(", "));
+    }
+    let player = |name: &Option<String>, rank: &Option<String>| match (name, rank) {
+        (Some(name), Some(rank)) => Some(format!("{name} {rank}")),
+        (Some(name), None) => Some(name.to_string()),
+        (None, Some(rank)) => Some(rank.to_string()),
+        (None, None) => None,
+    };
+    let players: Vec<String> = vec![
+        player(&info.black_player, &info.black_rank).map(|p| format!("Black: {p}")),
+        player(&info.white_player, &info.white_rank).map(|p| format!("White: {p}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !players.is_empty() {
+        lines.push(players.join(" / "));
+    }
+    lines.extend(game_result_text(goban));
+    lines
+}
+
+/// Returns the komi and recorded result, if the game has either.
+fn game_result_text(goban: &Goban) -> Option<String> {
+    let game_result: Vec<String> = vec![
+        goban.komi().map(|komi| format!("Komi: {komi}")),
+        goban.result().map(|result| format!("Result: {result}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if game_result.is_empty() {
+        return None;
+    }
+    Some(game_result.join(" / "))
+}
+
+/// Wraps text to lines of at most `max_chars` characters, breaking at whitespace where possible.
+///
+/// Line breaks in the text are preserved.
+fn wrap_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            let line_len = line.chars().count();
+            if line_len > 0 && line_len + 1 + word.len() <= max_chars {
+                line.push(' ');
+                line.extend(word);
+                continue;
+            }
+            if line_len > 0 {
+                lines.push(std::mem::take(&mut line));
+            }
+            while word.len() > max_chars {
+                lines.push(word.drain(..max_chars).collect());
+            }
+            line.extend(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Caption text with the score estimated from the position, taking the stones in the
+/// opponent's SGF territory as dead.
+fn score_text(goban: &Goban, rules: ScoringRules) -> String {
+    let dead_stones = goban.dead_stones().map(|s| (s.x, s.y)).collect();
+    let score = scoring::score(goban, &dead_stones, rules);
+    format!(
+        "Score: B {} / W {} ({})",
+        score.black,
+        score.white,
+        score.result()
+    )
+}
+
+/// Caption text with the judgments of the position before the first move.
+fn position_judgment_text(goban: &Goban, options: &RenderOptions) -> Option<String> {
+    let annotations = goban.annotations(0);
+    if annotations.is_empty() {
+        return None;
+    }
+    let symbols: Vec<&str> = annotations
+        .iter()
+        .map(|&annotation| options.style.annotation_symbol(annotation))
+        .collect();
+    Some(format!("Position: {}", symbols.join(" ")))
+}
+
+/// Caption text listing the annotations of the numbered moves.
+fn annotations_text(goban: &Goban, options: &RenderOptions) -> Option<String> {
+    let move_number_options = options.move_number_options?;
+    let entries: Vec<String> = goban
+        .moves()
+        .map(|(n, _)| n)
+        .filter(|&n| {
+            n >= move_number_options.start
+                && move_number_options.end.map(|end| n <= end).unwrap_or(true)
+        })
+        .filter_map(|n| {
+            let annotations = goban.annotations(n);
+            if annotations.is_empty() {
+                return None;
+            }
+            let symbols: Vec<&str> = annotations
+                .iter()
+                .map(|&annotation| options.style.annotation_symbol(annotation))
+                .collect();
+            let move_number = n + move_number_options.count_from - move_number_options.start;
+            Some(format!("{move_number} {}", symbols.join(" ")))
+        })
+        .collect();
+    if entries.is_empty() {
+        return None;
+    }
+    Some(format!("Annotations: {}", entries.join(", ")))
+}
+
+/// Caption text with the number of prisoners taken by each player.
+fn captures_text(goban: &Goban, options: &RenderOptions) -> String {
+    // Kifu pages only count captures up to the end of the page.
+    let last_move = match (options.kifu_pages, options.move_number_options) {
+        (Some(_), Some(move_number_options)) => move_number_options.end,
+        _ => None,
+    };
+    let prisoners = |color| -> u64 {
+        goban
+            .captures()
+            .filter(|&(n, c, _)| c == color && last_move.map(|end| n <= end).unwrap_or(true))
+            .map(|(_, _, count)| count)
+            .sum()
+    };
+    format!(
+        "Captures: B {} / W {}",
+        prisoners(StoneColor::Black),
+        prisoners(StoneColor::White)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::goban::{Annotation, Goban};
+    use crate::scoring::ScoringRules;
+
+    use super::{
+        annotated_stones, annotations_text, caption_lines, game_info_lines, score_text, wrap_text,
+        MoveNumberOptions, RenderOptions,
+    };
+
+    #[test]
+    fn annotations() {
+        let sgf = "(;SZ[9];B[ee]TE[1];W[cc];B[gc]DO[]GW[1])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let options = RenderOptions::default();
+        let stones = annotated_stones(&goban, &options);
+        assert_eq!(stones.len(), 1);
+        assert_eq!((stones[0].0.x, stones[0].0.y), (6, 2));
+        assert_eq!(annotations_text(&goban, &options), None);
+
+        let options = RenderOptions {
+            move_number_options: Some(MoveNumberOptions {
+                start: 1,
+                end: None,
+                count_from: 1,
+            }),
+            ..Default::default()
+        };
+        let stones = annotated_stones(&goban, &options);
+        assert_eq!(stones[0].1, &[Annotation::GoodMove]);
+        assert_eq!(
+            annotations_text(&goban, &options).unwrap(),
+            "Annotations: 1 !, 3 ?! W+"
+        );
+    }
+
+    #[test]
+    fn root_position_judgment() {
+        let goban = Goban::from_sgf("(;SZ[9]AB[cc]GW[2])", &Default::default(), true).unwrap();
+        let options = RenderOptions {
+            draw_annotations: true,
+            ..Default::default()
+        };
+        assert!(annotated_stones(&goban, &options).is_empty());
+        assert_eq!(
+            caption_lines(&goban, &options),
+            vec![("position-judgment", "Position: W+".to_string())]
+        );
+        assert!(caption_lines(&goban, &RenderOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn score_and_game_info() {
+        let sgf = "(;SZ[5]KM[0.5]RE[B+0.5]PB[Black])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let options = RenderOptions {
+            draw_score: true,
+            ..Default::default()
+        };
+        let captions: Vec<_> = caption_lines(&goban, &options)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect();
+        assert_eq!(
+            captions,
+            vec!["Score: B 0 / W 0.5 (W+0.5)", "Komi: 0.5 / Result: B+0.5"]
+        );
+        // With game info, the komi and result are only drawn above the board.
+        let options = RenderOptions {
+            draw_game_info: true,
+            ..options
+        };
+        assert_eq!(caption_lines(&goban, &options).len(), 1);
+        assert_eq!(
+            game_info_lines(&goban),
+            vec!["Black: Black", "Komi: 0.5 / Result: B+0.5"]
+        );
+    }
+
+    #[test]
+    fn score_rules() {
+        let sgf = "(;SZ[5]AB[ba][bb][bc][bd][be]AW[da][db][dc][dd][de])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        assert_eq!(
+            score_text(&goban, ScoringRules::Japanese),
+            "Score: B 5 / W 5 (Draw)"
+        );
+        assert_eq!(
+            score_text(&goban, ScoringRules::Chinese),
+            "Score: B 10 / W 10 (Draw)"
+        );
+    }
+
+    #[test]
+    fn wrap_words() {
+        let lines = wrap_text("The quick brown fox jumps over the lazy dog.", 15);
+        assert_eq!(
+            lines,
+            vec!["The quick brown", "fox jumps over", "the lazy dog."]
+        );
+    }
+
+    #[test]
+    fn wrap_preserves_line_breaks() {
+        let lines = wrap_text("Correct.\n\nBlack lives.", 20);
+        assert_eq!(lines, vec!["Correct.", "", "Black lives."]);
+    }
+
+    #[test]
+    fn wrap_long_word() {
+        let lines = wrap_text("a abcdefghij b", 4);
+        assert_eq!(lines, vec!["a", "abcd", "efgh", "ij b"]);
+    }
+}
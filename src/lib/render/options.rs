@@ -1,5 +1,12 @@
-use crate::{errors::UsageError, text::TileSet};
+use clap::builder::TypedValueParser;
 
+use crate::analysis::Analysis;
+use crate::errors::UsageError;
+use crate::goban::Goban;
+use crate::scoring::ScoringRules;
+use crate::text::{MarkupTileSet, TileSet};
+
+use super::overlay::VariationOverlay;
 use super::{GobanRange, GobanStyle};
 
 #[derive(Debug, Clone, Default)]
@@ -19,8 +26,59 @@ pub struct RenderOptions {
     pub draw_labels: bool,
     pub draw_lines: bool,
     pub draw_arrows: bool,
+    pub draw_territory: bool,
+    pub dim_dead_stones: bool,
+    pub draw_captures: bool,
+    pub draw_score: bool,
+    pub scoring_rules: ScoringRules,
+    pub last_moves: Option<u64>,
+    pub draw_comment: bool,
+    pub draw_game_info: bool,
+    pub transform: Transform,
     pub tileset: TileSet,
+    pub markup_tileset: MarkupTileSet,
     pub kifu_mode: bool,
+    pub kifu_pages: Option<u64>,
+    pub variation_markup: Option<VariationMarkup>,
+    pub variation_overlay: Option<VariationOverlay>,
+    pub analysis: Option<Analysis>,
+    pub draw_sgf_analysis: bool,
+    pub draw_annotations: bool,
+    pub annotation_footnotes: bool,
+}
+
+impl RenderOptions {
+    /// Splits the goban's moves into kifu pages of `kifu_pages` moves each.
+    ///
+    /// Returns options for rendering each page in turn, or `None` if not rendering kifu pages.
+    /// Moves keep the numbering requested by `count_from` across pages.
+    pub fn kifu_page_options(&self, goban: &Goban) -> Option<Vec<RenderOptions>> {
+        let page_length = self.kifu_pages?.max(1);
+        let count_from = self
+            .move_number_options
+            .map_or(1, |options| options.count_from);
+        let first_move = goban.moves().next().map(|(n, _)| n).unwrap_or(1);
+        let last_move = goban.moves().last().map(|(n, _)| n).unwrap_or(first_move);
+        let pages = (first_move..=last_move)
+            .step_by(page_length as usize)
+            .map(|start| RenderOptions {
+                move_number_options: Some(MoveNumberOptions {
+                    start,
+                    end: Some(start + page_length - 1),
+                    count_from: start - 1 + count_from,
+                }),
+                ..self.clone()
+            })
+            .collect();
+        Some(pages)
+    }
+}
+
+/// How to draw the moves of the selected node's child variations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum VariationMarkup {
+    Letters,
+    Ghosts,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default, clap::Parser)]
@@ -36,6 +94,26 @@ pub struct NodeDescription {
     pub node_number: NodeNumber,
 }
 
+impl std::str::FromStr for NodeDescription {
+    type Err = UsageError;
+
+    /// Parses a node description of the form `[GAME:]VARIATION:NODE`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || UsageError::InvalidNodeDescription(s.to_string());
+        let parts: Vec<_> = s.trim().split(':').collect();
+        let (game_number, variation, node_number) = match parts[..] {
+            [variation, node_number] => ("0", variation, node_number),
+            [game_number, variation, node_number] => (game_number, variation, node_number),
+            _ => return Err(error()),
+        };
+        Ok(NodeDescription {
+            game_number: game_number.parse().map_err(|_| error())?,
+            variation: variation.parse().map_err(|_| error())?,
+            node_number: node_number.parse().map_err(|_| error())?,
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum NodeNumber {
     Number(u64),
@@ -59,6 +137,75 @@ impl std::str::FromStr for NodeNumber {
     }
 }
 
+impl std::fmt::Display for NodeNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeNumber::Number(n) => write!(f, "{n}"),
+            NodeNumber::Last => write!(f, "last"),
+        }
+    }
+}
+
+// Transformation of board coordinates. The board is transposed, then flipped, then rotated.
+// (Not a doc comment, since clap would use it as help text.)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, clap::Parser)]
+pub struct Transform {
+    /// Rotate the board clockwise by DEGREES.
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value = "0",
+        value_parser = clap::builder::PossibleValuesParser::new(["0", "90", "180", "270"])
+            .map(|s| s.parse::<u16>().unwrap())
+    )]
+    pub rotate: u16,
+    /// Flip the board left to right.
+    #[arg(long)]
+    pub flip_horizontal: bool,
+    /// Flip the board top to bottom.
+    #[arg(long)]
+    pub flip_vertical: bool,
+    /// Swap the board's rows and columns.
+    #[arg(long)]
+    pub transpose: bool,
+}
+
+impl Transform {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns the size of a board of the given size after transformation.
+    pub fn size(&self, size: (u8, u8)) -> (u8, u8) {
+        if self.transpose ^ (self.rotate % 180 == 90) {
+            (size.1, size.0)
+        } else {
+            size
+        }
+    }
+
+    /// Returns the transformed coordinates of a point on a board of the given size.
+    pub fn point(&self, point: (u8, u8), size: (u8, u8)) -> (u8, u8) {
+        let (mut x, mut y) = point;
+        let (mut width, mut height) = size;
+        if self.transpose {
+            (x, y) = (y, x);
+            (width, height) = (height, width);
+        }
+        if self.flip_horizontal {
+            x = width - 1 - x;
+        }
+        if self.flip_vertical {
+            y = height - 1 - y;
+        }
+        for _ in 0..self.rotate / 90 {
+            (x, y) = (height - 1 - y, x);
+            (width, height) = (height, width);
+        }
+        (x, y)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct MoveNumberOptions {
     pub start: u64,
@@ -108,3 +255,94 @@ impl std::str::FromStr for BoardSideSet {
         Ok(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Goban;
+
+    use super::{MoveNumberOptions, RenderOptions, Transform};
+
+    #[test]
+    fn transform_points() {
+        let size = (19, 13);
+        let rotate = |rotate| Transform {
+            rotate,
+            ..Default::default()
+        };
+        assert_eq!(rotate(90).point((0, 0), size), (12, 0));
+        assert_eq!(rotate(90).size(size), (13, 19));
+        assert_eq!(rotate(180).point((0, 0), size), (18, 12));
+        assert_eq!(rotate(270).point((0, 0), size), (0, 18));
+        assert_eq!(rotate(270).point((3, 2), size), (2, 15));
+        let transpose = Transform {
+            transpose: true,
+            ..Default::default()
+        };
+        assert_eq!(transpose.point((3, 2), size), (2, 3));
+        let flip = Transform {
+            flip_horizontal: true,
+            flip_vertical: true,
+            ..Default::default()
+        };
+        assert_eq!(flip.point((3, 2), size), (15, 10));
+        assert_eq!(flip.point((3, 2), size), rotate(180).point((3, 2), size));
+    }
+
+    #[test]
+    fn kifu_page_options() {
+        let goban = Goban::from_sgf(
+            "(;B[aa];W[ba];B[ca];W[da];B[ea];W[fa];B[ga])",
+            &Default::default(),
+            true,
+        )
+        .unwrap();
+        let options = RenderOptions {
+            kifu_mode: true,
+            kifu_pages: Some(3),
+            ..Default::default()
+        };
+        let pages: Vec<_> = options
+            .kifu_page_options(&goban)
+            .unwrap()
+            .iter()
+            .map(|page| {
+                let move_number_options = page.move_number_options.unwrap();
+                (move_number_options.start, move_number_options.end)
+            })
+            .collect();
+        assert_eq!(pages, vec![(1, Some(3)), (4, Some(6)), (7, Some(9))]);
+    }
+
+    #[test]
+    fn kifu_page_options_count_from() {
+        let goban = Goban::from_sgf(
+            "(;B[aa];W[ba];B[ca];W[da];B[ea])",
+            &Default::default(),
+            true,
+        )
+        .unwrap();
+        let options = RenderOptions {
+            kifu_mode: true,
+            kifu_pages: Some(3),
+            move_number_options: Some(MoveNumberOptions {
+                start: 1,
+                end: None,
+                count_from: 101,
+            }),
+            ..Default::default()
+        };
+        let count_from: Vec<_> = options
+            .kifu_page_options(&goban)
+            .unwrap()
+            .iter()
+            .map(|page| page.move_number_options.unwrap().count_from)
+            .collect();
+        assert_eq!(count_from, vec![101, 104]);
+    }
+
+    #[test]
+    fn no_kifu_pages() {
+        let goban = Goban::from_sgf("(;B[aa])", &Default::default(), true).unwrap();
+        assert!(RenderOptions::default().kifu_page_options(&goban).is_none());
+    }
+}
@@ -0,0 +1,163 @@
+use sgf_parse::GameTree;
+
+use super::{MoveNumberOptions, NodeDescription, NodeNumber, RenderOptions};
+
+use crate::errors::GobanError;
+use crate::goban::{game_root, Goban, GobanOptions};
+use crate::sgf_traversal::variation_nodes;
+
+/// A variation whose moves after the selected node are numbered over the node's position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VariationOverlay {
+    pub variation: u64,
+    /// Number of moves to number (or all the variation's moves if `None`).
+    pub moves: Option<u64>,
+}
+
+/// Returns the goban and options for drawing the overlay's moves numbered from 1 over the position
+/// at the selected node.
+///
+/// The overlay is drawn as a single kifu page, so moves played on a point where an earlier
+/// overlay stone is shown are listed below the diagram.
+pub fn overlay(
+    collection: &[GameTree],
+    options: &RenderOptions,
+    overlay: &VariationOverlay,
+    goban_options: &GobanOptions,
+) -> Result<(Goban, RenderOptions), GobanError> {
+    let node_description = options.node_description;
+    let root_node = game_root(collection, node_description.game_number)?;
+    let base_nodes: Vec<_> = variation_nodes(root_node, node_description.variation)?
+        .map(|node| node.sgf_node)
+        .collect();
+    let base_index = match node_description.node_number {
+        NodeNumber::Number(n) => n as usize,
+        NodeNumber::Last => base_nodes.len() - 1,
+    };
+    let base_node = base_nodes
+        .get(base_index)
+        .ok_or(GobanError::InsufficientSgfNodes)?;
+    let overlay_nodes: Vec<_> = variation_nodes(root_node, overlay.variation)?
+        .map(|node| node.sgf_node)
+        .collect();
+    if !overlay_nodes
+        .get(base_index)
+        .is_some_and(|node| std::ptr::eq(*node, *base_node))
+    {
+        return Err(GobanError::UnrelatedVariation);
+    }
+
+    let node_descriptions: Vec<NodeDescription> = (base_index..overlay_nodes.len())
+        .map(|n| NodeDescription {
+            game_number: node_description.game_number,
+            variation: overlay.variation,
+            node_number: NodeNumber::Number(n as u64),
+        })
+        .collect();
+    let gobans = Goban::from_collection(collection, &node_descriptions, goban_options)?;
+    let last_move = |goban: &Goban| goban.moves().last().map(|(n, _)| n).unwrap_or(0);
+    let start = last_move(&gobans[0]) + 1;
+    let requested_end = overlay.moves.map(|moves| start + moves - 1);
+    // Stop at the node where the last requested move is played.
+    let index = requested_end
+        .and_then(|end| gobans.iter().position(|goban| last_move(goban) >= end))
+        .unwrap_or(gobans.len() - 1);
+    let goban = gobans.into_iter().nth(index).unwrap();
+    let end = requested_end.unwrap_or(u64::MAX).min(last_move(&goban));
+    if end < start {
+        return Err(GobanError::InsufficientSgfNodes);
+    }
+
+    let options = RenderOptions {
+        node_description: node_descriptions[index],
+        move_number_options: Some(MoveNumberOptions {
+            start,
+            end: Some(end),
+            count_from: 1,
+        }),
+        kifu_mode: true,
+        kifu_pages: Some(end - start + 1),
+        ..options.clone()
+    };
+    Ok((goban, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::GobanError;
+    use crate::render::{repeated_moves, NodeDescription, NodeNumber, RenderOptions};
+
+    use super::{overlay, VariationOverlay};
+
+    // White's stone at bb is captured in a ko, then played again.
+    static TEST_DATA: &str = "(;SZ[9]AB[ba][ab][bc]AW[ca][db][cc];B[gg]\
+                              (;W[hh])\
+                              (;W[bb];B[cb];W[ff];B[hh];W[bb]))";
+
+    fn options(node_number: u64) -> RenderOptions {
+        RenderOptions {
+            node_description: NodeDescription {
+                node_number: NodeNumber::Number(node_number),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn numbers_from_base_position() {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        let variation_overlay = VariationOverlay {
+            variation: 1,
+            moves: Some(3),
+        };
+        let (goban, options) = overlay(
+            &collection,
+            &options(1),
+            &variation_overlay,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(goban.moves().count(), 4);
+        let move_number_options = options.move_number_options.unwrap();
+        assert_eq!(move_number_options.start, 2);
+        assert_eq!(move_number_options.end, Some(4));
+        assert_eq!(move_number_options.count_from, 1);
+        assert_eq!(options.node_description.variation, 1);
+        assert_eq!(options.node_description.node_number, NodeNumber::Number(4));
+    }
+
+    #[test]
+    fn lists_repeated_moves() {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        let variation_overlay = VariationOverlay {
+            variation: 1,
+            moves: None,
+        };
+        let (goban, options) = overlay(
+            &collection,
+            &options(1),
+            &variation_overlay,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(options.move_number_options.unwrap().end, Some(6));
+        assert_eq!(repeated_moves(&goban, &options), vec![(5, 1)]);
+    }
+
+    #[test]
+    fn variation_must_contain_node() {
+        let collection = sgf_parse::parse(TEST_DATA).unwrap();
+        let variation_overlay = VariationOverlay {
+            variation: 1,
+            moves: None,
+        };
+        let result = overlay(
+            &collection,
+            &options(2),
+            &variation_overlay,
+            &Default::default(),
+        );
+        assert!(matches!(result, Err(GobanError::UnrelatedVariation)));
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{diagram_stones, get_move_numbers, RenderOptions};
+
+use crate::errors::GobanError;
+use crate::goban::{Goban, StoneColor};
+
+/// Renders the goban in Sensei's Library diagram syntax.
+pub fn render(goban: &Goban, options: &RenderOptions) -> Result<String, GobanError> {
+    let transformed;
+    let goban = if options.transform.is_identity() {
+        goban
+    } else {
+        transformed = goban.transformed(&options.transform);
+        &transformed
+    };
+    let (x_range, y_range) = options.goban_range.get_ranges(goban, options)?;
+    let (board_width, board_height) = goban.size();
+    let stones: HashMap<(u8, u8), StoneColor> = diagram_stones(goban, options)
+        .into_iter()
+        .map(|stone| ((stone.x, stone.y), stone.color))
+        .collect();
+
+    let mut header = "$$".to_string();
+    let mut cells: HashMap<(u8, u8), char> = HashMap::new();
+    let mut first_number = 1;
+    if let Some(move_number_options) = options.move_number_options {
+        // Diagrams number moves 1 to 10 (drawn as 0), alternating from the first move's color.
+        let move_numbers = get_move_numbers(goban, options);
+        if let Some((first, stone)) = move_numbers.first() {
+            let alternating = move_numbers.iter().all(|(n, s)| {
+                let first_color = (n - first) % 2 == 0;
+                (s.color == stone.color) == first_color
+            });
+            let last = move_numbers.last().map(|(n, _)| *n).unwrap_or(*first);
+            if last - first >= 10 || !alternating {
+                return Err(GobanError::UnrepresentableMoveNumbers(
+                    "Sensei's Library diagrams can only number up to 10 moves of alternating color",
+                ));
+            }
+            header.push(match stone.color {
+                StoneColor::Black => 'B',
+                StoneColor::White => 'W',
+            });
+            for (n, stone) in &move_numbers {
+                let digit = std::char::from_digit(((n - first + 1) % 10) as u32, 10).unwrap();
+                cells.insert((stone.x, stone.y), digit);
+            }
+            first_number = first + move_number_options.count_from - move_number_options.start;
+        }
+    }
+    if !options.label_sides.is_empty() {
+        header.push_str(&format!("c{board_width}"));
+    }
+    if first_number != 1 {
+        header.push_str(&format!("m{first_number}"));
+    }
+
+    let markup = [
+        (
+            options.draw_marks,
+            goban.marks().collect::<Vec<_>>(),
+            ['M', 'Z', 'P'],
+        ),
+        (
+            options.draw_triangles,
+            goban.triangles().collect(),
+            ['T', 'Y', 'Q'],
+        ),
+        (
+            options.draw_circles,
+            goban.circles().collect(),
+            ['C', 'B', 'W'],
+        ),
+        (
+            options.draw_squares,
+            goban.squares().collect(),
+            ['S', '#', '@'],
+        ),
+    ];
+    for (draw, points, [empty, black, white]) in markup.iter() {
+        if !draw {
+            continue;
+        }
+        for point in points {
+            if cells.contains_key(point) {
+                continue;
+            }
+            let c = match stones.get(point) {
+                None => *empty,
+                Some(StoneColor::Black) => *black,
+                Some(StoneColor::White) => *white,
+            };
+            cells.insert(*point, c);
+        }
+    }
+    if options.draw_labels {
+        // Only single lowercase letters on empty points can be drawn as labels.
+        for (point, text) in goban.labels() {
+            let mut chars = text.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                if c.is_ascii_alphabetic() && !stones.contains_key(point) {
+                    cells.entry(*point).or_insert(c.to_ascii_lowercase());
+                }
+            }
+        }
+    }
+
+    let hoshis: HashSet<(u8, u8)> = goban.hoshi_points().collect();
+    let left_edge = x_range.start == 0;
+    let right_edge = x_range.end == board_width;
+    let border = {
+        let mut line = "$$ ".to_string();
+        if left_edge {
+            line.push('+');
+        }
+        line.push_str(&"-".repeat(2 * x_range.len() + 1));
+        if right_edge {
+            line.push('+');
+        }
+        line
+    };
+
+    let mut lines = vec![header];
+    if y_range.start == 0 {
+        lines.push(border.clone());
+    }
+    for y in y_range.clone() {
+        let mut line = "$$".to_string();
+        if left_edge {
+            line.push_str(" |");
+        }
+        for x in x_range.clone() {
+            let c = match cells.get(&(x, y)) {
+                Some(c) => *c,
+                None => match stones.get(&(x, y)) {
+                    Some(StoneColor::Black) => 'X',
+                    Some(StoneColor::White) => 'O',
+                    None if hoshis.contains(&(x, y)) => ',',
+                    None => '.',
+                },
+            };
+            line.push(' ');
+            line.push(c);
+        }
+        if right_edge {
+            line.push_str(" |");
+        }
+        lines.push(line);
+    }
+    if y_range.end == board_height {
+        lines.push(border);
+    }
+
+    let diagram_point = |(x, y): (u8, u8)| -> Option<String> {
+        if x_range.contains(&x) && y_range.contains(&y) {
+            let column = (b'A' + x - x_range.start) as char;
+            Some(format!("{column}{}", y - y_range.start + 1))
+        } else {
+            None
+        }
+    };
+    let mut pairs = vec![];
+    if options.draw_arrows {
+        pairs.extend(goban.arrows().map(|(p1, p2)| ("AR", p1, p2)));
+    }
+    if options.draw_lines {
+        pairs.extend(goban.lines().map(|(p1, p2)| ("LN", p1, p2)));
+    }
+    pairs.sort_unstable();
+    for (kind, p1, p2) in pairs {
+        if let (Some(p1), Some(p2)) = (diagram_point(p1), diagram_point(p2)) {
+            lines.push(format!("$$ {{{kind} {p1} {p2}}}"));
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{GobanRange, MoveNumberOptions};
+    use crate::{Goban, RenderOptions};
+
+    use super::render;
+
+    #[test]
+    fn full_board() {
+        let sgf = "(;SZ[9]AB[cc][dc]AW[cd][dd]TR[cc]CR[dd]LB[ee:A]AR[aa:bb])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let options = RenderOptions {
+            draw_triangles: true,
+            draw_circles: true,
+            draw_labels: true,
+            draw_arrows: true,
+            ..Default::default()
+        };
+        let diagram = render(&goban, &options).unwrap();
+        let expected = "\
+$$
+$$ +-------------------+
+$$ | . . . . . . . . . |
+$$ | . . . . . . . . . |
+$$ | . . Y X . . , . . |
+$$ | . . O W . . . . . |
+$$ | . . . . a . . . . |
+$$ | . . . . . . . . . |
+$$ | . . , . . . , . . |
+$$ | . . . . . . . . . |
+$$ | . . . . . . . . . |
+$$ +-------------------+
+$$ {AR A1 B2}";
+        assert_eq!(diagram, expected);
+    }
+
+    #[test]
+    fn move_numbers_in_range() {
+        let sgf = "(;SZ[19];B[pd];W[dd];B[pq];W[dp];B[fq];W[cn];B[jp];W[qo];B[qp];W[pn];B[cc])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let options = RenderOptions {
+            goban_range: GobanRange::Ranged(11..19, 12..19),
+            move_number_options: Some(MoveNumberOptions {
+                start: 8,
+                end: Some(10),
+                count_from: 8,
+            }),
+            ..Default::default()
+        };
+        let diagram = render(&goban, &options).unwrap();
+        let expected = "\
+$$Wm8
+$$ . . . . . . . . |
+$$ . . . . 3 . . . |
+$$ . . . . . 1 . . |
+$$ . . . . , 2 . . |
+$$ . . . . X . . . |
+$$ . . . . . . . . |
+$$ . . . . . . . . |
+$$ -----------------+";
+        assert_eq!(diagram, expected);
+    }
+
+    #[test]
+    fn coordinates_without_moves_in_range() {
+        let goban = Goban::from_sgf("(;SZ[9]AB[cc])", &Default::default(), true).unwrap();
+        let options = RenderOptions {
+            label_sides: "nw".parse().unwrap(),
+            move_number_options: Some(MoveNumberOptions {
+                start: 1,
+                end: None,
+                count_from: 1,
+            }),
+            ..Default::default()
+        };
+        let diagram = render(&goban, &options).unwrap();
+        assert_eq!(diagram.lines().next(), Some("$$c9"));
+    }
+
+    #[test]
+    fn too_many_move_numbers() {
+        let sgf = "(;SZ[9];B[aa];W[ba];B[ca];W[da];B[ea];W[fa];B[ga];W[ha];B[ia];W[ab];B[bb])";
+        let goban = Goban::from_sgf(sgf, &Default::default(), true).unwrap();
+        let options = RenderOptions {
+            move_number_options: Some(MoveNumberOptions {
+                start: 1,
+                end: None,
+                count_from: 1,
+            }),
+            ..Default::default()
+        };
+        assert!(render(&goban, &options).is_err());
+    }
+}
@@ -1,11 +1,16 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::ops::Range;
 
 use minidom::Element;
 
 use super::options::BoardSide;
-use super::{board_label_text, BoardSideSet, GobanStyle, MoveNumberOptions, RenderOptions};
+use super::{
+    annotated_stones, board_label_text, caption_lines, diagram_stones, game_info_lines,
+    get_move_numbers, repeated_moves, wrap_text, BoardSideSet, GobanStyle, LastMoveMarker,
+    MoveNumberOptions, RenderOptions, VariationMarkup,
+};
 
+use crate::analysis::Analysis;
 use crate::errors::GobanError;
 use crate::goban::{Goban, Stone, StoneColor};
 
@@ -14,12 +19,29 @@ pub static NAMESPACE: &str = "http://www.w3.org/2000/svg";
 static BOARD_MARGIN: f64 = 0.64;
 static LABEL_MARGIN: f64 = 0.8;
 static REPEATED_MOVES_MARGIN: f64 = 0.32;
+static CAPTION_MARGIN: f64 = 0.32;
+static CAPTION_LINE_HEIGHT: f64 = 1.25;
+// Rough average glyph width as a fraction of the font size, used for word wrapping.
+static CAPTION_CHAR_WIDTH: f64 = 0.55;
 
 static FONT_FAMILY: &str = "Inter";
 static FONT_SIZE: f64 = 0.45;
+static ANNOTATION_FONT_SIZE: f64 = 0.26;
 static FONT_WEIGHT: usize = 700;
 
 pub fn render(goban: &Goban, options: &RenderOptions) -> Result<Element, GobanError> {
+    let analysis = options
+        .analysis
+        .as_ref()
+        .or(goban.analysis().filter(|_| options.draw_sgf_analysis))
+        .map(|analysis| analysis.transformed(&options.transform, goban.size()));
+    let transformed;
+    let goban = if options.transform.is_identity() {
+        goban
+    } else {
+        transformed = goban.transformed(&options.transform);
+        &transformed
+    };
     let (x_range, y_range) = options.goban_range.get_ranges(goban, options)?;
     let width = x_range.end - x_range.start;
     let height = y_range.end - y_range.start;
@@ -48,12 +70,28 @@ pub fn render(goban: &Goban, options: &RenderOptions) -> Result<Element, GobanEr
     let diagram_width = f64::from(width) - 1.0 + 2.0 * BOARD_MARGIN + left_margin + right_margin;
 
     let (diagram, diagram_height) = {
-        let board = build_board(goban, options);
+        let header = if options.draw_game_info {
+            draw_text_block(
+                "game-info",
+                &game_info_lines(goban),
+                true,
+                width,
+                CAPTION_MARGIN,
+                options,
+            )
+        } else {
+            None
+        };
+        let header_height = match &header {
+            Some((_, height)) => height + 2.0 * CAPTION_MARGIN,
+            None => 0.0,
+        };
+        let board = build_board(goban, options, analysis.as_ref());
         let board_view = {
             let board_view_transform = format!(
                 "translate({}, {})",
                 format_float(BOARD_MARGIN + left_margin - f64::from(x_range.start)),
-                format_float(BOARD_MARGIN + top_margin - f64::from(y_range.start))
+                format_float(BOARD_MARGIN + top_margin + header_height - f64::from(y_range.start))
             );
             Element::builder("g", NAMESPACE)
                 .attr("id", "board-view")
@@ -68,18 +106,24 @@ pub fn render(goban: &Goban, options: &RenderOptions) -> Result<Element, GobanEr
             .attr("id", "diagram")
             .attr("transform", transform)
             .append(board_view);
+        if let Some((element, _)) = header {
+            diagram_builder = diagram_builder.append(element);
+        }
 
         if !options.label_sides.is_empty() {
             let goban_size = goban.size();
             diagram_builder = diagram_builder.append(draw_board_labels(
                 x_range,
                 goban_size.1 - height - y_range.start + 1..goban_size.1 - y_range.start + 1,
+                header_height,
                 options,
             ));
         }
 
-        let mut diagram_height =
-            f64::from(height) - 1.0 + 2.0 * BOARD_MARGIN + top_margin + bottom_margin;
+        let mut diagram_height = header_height + f64::from(height) - 1.0
+            + 2.0 * BOARD_MARGIN
+            + top_margin
+            + bottom_margin;
         if options.kifu_mode {
             if let Some((element, element_height)) = draw_repeated_stones(
                 goban,
@@ -91,6 +135,25 @@ pub fn render(goban: &Goban, options: &RenderOptions) -> Result<Element, GobanEr
                 diagram_height += element_height + REPEATED_MOVES_MARGIN * 2.0;
             }
         }
+        let caption_lines = caption_lines(goban, options);
+        for (id, text) in &caption_lines {
+            let (element, element_height) =
+                draw_caption(id, text, width, diagram_height + CAPTION_MARGIN, options);
+            diagram_builder = diagram_builder.append(element);
+            diagram_height += element_height + CAPTION_MARGIN;
+        }
+        if !caption_lines.is_empty() {
+            diagram_height += CAPTION_MARGIN;
+        }
+        if options.draw_comment {
+            let lines: Vec<String> = goban.comment().into_iter().map(str::to_string).collect();
+            if let Some((element, element_height)) =
+                draw_text_block("comment", &lines, false, width, diagram_height, options)
+            {
+                diagram_builder = diagram_builder.append(element);
+                diagram_height += element_height + CAPTION_MARGIN;
+            }
+        }
 
         (diagram_builder.build(), diagram_height)
     };
@@ -123,13 +186,21 @@ pub fn render(goban: &Goban, options: &RenderOptions) -> Result<Element, GobanEr
 }
 
 /// Draws a goban with squares of unit size.
-fn build_board(goban: &Goban, options: &RenderOptions) -> Element {
+fn build_board(goban: &Goban, options: &RenderOptions, analysis: Option<&Analysis>) -> Element {
     let mut group_builder = Element::builder("g", NAMESPACE)
         .attr("id", "goban")
         .attr("clip-path", "url(#board-clip)")
         .append(build_board_lines_group(goban, options))
         .append(build_stones_group(goban, options));
 
+    if options.draw_territory && goban.territory().next().is_some() {
+        group_builder = group_builder.append(build_territory_group(goban, options));
+    }
+
+    if let Some(analysis) = analysis.filter(|analysis| !analysis.ownership.is_empty()) {
+        group_builder = group_builder.append(build_ownership_group(analysis, options));
+    }
+
     let move_numbers = get_move_numbers(goban, options);
     let no_markup_points: HashSet<(u8, u8)> = move_numbers
         .iter()
@@ -168,12 +239,29 @@ fn build_board(goban: &Goban, options: &RenderOptions) -> Element {
     if options.draw_labels {
         group_builder = group_builder.append(build_label_group(goban, options, &no_markup_points));
     }
+    if let Some(markup) = options.variation_markup {
+        group_builder = group_builder.append(build_variations_group(goban, options, markup));
+    }
     if options.draw_lines {
         group_builder = group_builder.append(build_line_group(goban, options));
     }
     if options.draw_arrows {
         group_builder = group_builder.append(build_arrow_group(goban, options));
     }
+    if let Some(analysis) = analysis.filter(|analysis| !analysis.candidates.is_empty()) {
+        group_builder = group_builder.append(build_candidates_group(goban, analysis, options));
+    }
+    if options.draw_annotations {
+        group_builder = group_builder.append(build_annotations_group(goban, options));
+    }
+    if let Some(count) = options.last_moves {
+        group_builder = group_builder.append(build_last_moves_group(
+            goban,
+            options,
+            count,
+            &no_markup_points,
+        ));
+    }
 
     group_builder.build()
 }
@@ -227,26 +315,128 @@ fn build_stones_group(goban: &Goban, options: &RenderOptions) -> Element {
     let mut group_builder = Element::builder("g", NAMESPACE)
         .attr("id", "stones")
         .attr("stroke", "none");
-    let mut stones: Vec<Stone> = if options.kifu_mode {
-        // For each intersection draw the first numbered stone, or the last non-numbered stone.
-        let mut stones: HashMap<(u8, u8), Stone> = HashMap::new();
-        let mut numbered_stones: HashMap<(u8, u8), Stone> = HashMap::new();
-        for (n, stone) in goban.moves() {
-            if n >= options.move_number_options.unwrap().start {
-                numbered_stones.entry((stone.x, stone.y)).or_insert(stone);
-            }
-            stones.insert((stone.x, stone.y), stone);
-        }
-        for (key, stone) in numbered_stones {
-            stones.insert(key, stone);
-        }
-        stones.into_values().collect()
+    let stones = diagram_stones(goban, options);
+    let dead_stones: HashSet<Stone> = if options.dim_dead_stones {
+        goban.dead_stones().collect()
     } else {
-        goban.stones().collect()
+        HashSet::new()
     };
-    stones.sort_by_key(|stone| (stone.y, stone.x));
     for stone in stones {
-        group_builder = group_builder.append(draw_stone(stone, &options.style));
+        let mut element = draw_stone(stone, &options.style);
+        if dead_stones.contains(&stone) {
+            element.set_attr("opacity", format_float(options.style.dead_stone_opacity()));
+        }
+        group_builder = group_builder.append(element);
+    }
+    group_builder.build()
+}
+
+fn build_territory_group(goban: &Goban, options: &RenderOptions) -> Element {
+    let mut group_builder = Element::builder("g", NAMESPACE)
+        .attr("id", "territory")
+        .attr("stroke", options.style.line_color())
+        .attr("stroke-width", format_float(options.style.line_width()));
+    let mut territory: Vec<_> = goban.territory().collect();
+    territory.sort_unstable_by_key(|&(point, _)| point);
+    for (point, color) in territory {
+        group_builder =
+            group_builder.append(draw_territory(point.0, point.1, color, &options.style));
+    }
+    group_builder.build()
+}
+
+fn build_ownership_group(analysis: &Analysis, options: &RenderOptions) -> Element {
+    let mut group_builder = Element::builder("g", NAMESPACE)
+        .attr("id", "ownership")
+        .attr("stroke", "none");
+    let mut ownership: Vec<_> = analysis.ownership.iter().collect();
+    ownership.sort_unstable_by_key(|&(point, _)| point);
+    for (&(x, y), &value) in ownership {
+        if value != 0.0 {
+            group_builder = group_builder.append(draw_ownership(x, y, value, &options.style));
+        }
+    }
+    group_builder.build()
+}
+
+fn build_candidates_group(goban: &Goban, analysis: &Analysis, options: &RenderOptions) -> Element {
+    let mut group_builder = Element::builder("g", NAMESPACE)
+        .attr("id", "candidates")
+        .attr("text-anchor", "middle");
+    let max_visits = analysis
+        .candidates
+        .iter()
+        .filter_map(|candidate| candidate.visits)
+        .max()
+        .unwrap_or(0);
+    for (i, candidate) in analysis.candidates.iter().enumerate() {
+        let lines: Vec<String> = [
+            candidate
+                .winrate
+                .map(|winrate| format!("{:.0}%", winrate * 100.0)),
+            candidate.visits.map(format_visits),
+            candidate
+                .score_lead
+                .map(|score_lead| format!("{score_lead:+.1}")),
+        ]
+        .iter()
+        .flatten()
+        .cloned()
+        .collect();
+        let (fill, opacity) = if i == 0 {
+            (options.style.best_candidate_color(), 1.0)
+        } else {
+            let share = match candidate.visits {
+                Some(visits) if max_visits > 0 => visits as f64 / max_visits as f64,
+                _ => 1.0,
+            };
+            (options.style.candidate_color(), 0.4 + 0.6 * share)
+        };
+        let (x, y) = candidate.point;
+        let stone_color = goban.stone_color(x, y);
+        group_builder = group_builder.append(draw_candidate(
+            x,
+            y,
+            &lines,
+            stone_color,
+            (fill, opacity),
+            &options.style,
+        ));
+    }
+    group_builder.build()
+}
+
+/// Abbreviates a visit count to fit inside a candidate label (e.g. '1.2k').
+fn format_visits(visits: u64) -> String {
+    match visits {
+        0..=999 => visits.to_string(),
+        1000..=999_999 => format!(
+            "{}k",
+            format_float((visits as f64 / 1e3 * 10.0).round() / 10.0)
+        ),
+        _ => format!(
+            "{}M",
+            format_float((visits as f64 / 1e6 * 10.0).round() / 10.0)
+        ),
+    }
+}
+
+fn build_annotations_group(goban: &Goban, options: &RenderOptions) -> Element {
+    let mut group_builder = Element::builder("g", NAMESPACE)
+        .attr("id", "annotations")
+        .attr("text-anchor", "middle")
+        .attr("font-size", format_float(ANNOTATION_FONT_SIZE));
+    for (stone, annotations) in annotated_stones(goban, options) {
+        let symbols: Vec<&str> = annotations
+            .iter()
+            .map(|&annotation| options.style.annotation_symbol(annotation))
+            .collect();
+        group_builder = group_builder.append(draw_annotation(
+            stone.x,
+            stone.y,
+            &symbols.join(" "),
+            &options.style,
+        ));
     }
     group_builder.build()
 }
@@ -280,30 +470,6 @@ fn build_move_numbers_group(
     group_builder.build()
 }
 
-fn get_move_numbers(goban: &Goban, options: &RenderOptions) -> Vec<(u64, Stone)> {
-    let move_number_options = match options.move_number_options {
-        Some(move_number_options) => move_number_options,
-        None => return Vec::new(),
-    };
-    let numbered_moves = goban
-        .moves()
-        .skip_while(|&(n, _)| n < move_number_options.start)
-        .take_while(|&(n, _)| move_number_options.end.map(|end| n <= end).unwrap_or(true));
-    let mut move_numbers: HashMap<(u8, u8), (u64, Stone)> = HashMap::new();
-    for (n, stone) in numbered_moves {
-        if options.kifu_mode {
-            // In Kifu mode we care about the first numbered stone played.
-            move_numbers.entry((stone.x, stone.y)).or_insert((n, stone));
-        } else {
-            // Otherwise we care about the last numbered stone played.
-            move_numbers.insert((stone.x, stone.y), (n, stone));
-        }
-    }
-    let mut move_numbers: Vec<(u64, Stone)> = move_numbers.values().copied().collect();
-    move_numbers.sort_unstable_by_key(|(n, _)| *n);
-    move_numbers
-}
-
 fn build_marks_group(
     goban: &Goban,
     options: &RenderOptions,
@@ -415,6 +581,55 @@ fn build_label_group(
     group_builder.build()
 }
 
+fn build_variations_group(
+    goban: &Goban,
+    options: &RenderOptions,
+    markup: VariationMarkup,
+) -> Element {
+    let mut group_builder = Element::builder("g", NAMESPACE).attr("id", "variations");
+    // Letters follow the branch index, so a child without a move still uses up its letter.
+    let letters: Vec<char> = ('A'..='Z').chain('a'..='z').collect();
+    for (branch, stone) in goban.next_moves() {
+        let letter = match letters.get(branch) {
+            Some(letter) => letter,
+            None => break,
+        };
+        let is_main_line = branch == 0;
+        match markup {
+            VariationMarkup::Letters => {
+                let stone_color = goban.stone_color(stone.x, stone.y);
+                let mut label = draw_label(
+                    stone.x,
+                    stone.y,
+                    &letter.to_string(),
+                    stone_color,
+                    &options.style,
+                );
+                if is_main_line {
+                    for child in label.children_mut().filter(|child| child.name() == "text") {
+                        child.set_attr("fill", options.style.selected_color(stone_color));
+                    }
+                }
+                group_builder = group_builder.append(label);
+            }
+            VariationMarkup::Ghosts => {
+                let mut ghost = draw_stone(stone, &options.style);
+                ghost.set_attr("opacity", format_float(options.style.ghost_stone_opacity()));
+                group_builder = group_builder.append(ghost);
+                if is_main_line {
+                    group_builder = group_builder.append(draw_circle(
+                        stone.x,
+                        stone.y,
+                        Some(stone.color),
+                        &options.style,
+                    ));
+                }
+            }
+        }
+    }
+    group_builder.build()
+}
+
 fn build_line_group(goban: &Goban, options: &RenderOptions) -> Element {
     let mut group_builder = Element::builder("g", NAMESPACE)
         .attr("id", "markup-lines")
@@ -461,12 +676,17 @@ fn build_arrow_group(goban: &Goban, options: &RenderOptions) -> Element {
 ///
 /// Assumes lines are a unit apart, offset by `BOARD_MARGIN`.
 /// Respects `LABEL_MARGIN`.
-fn draw_board_labels(x_range: Range<u8>, y_range: Range<u8>, options: &RenderOptions) -> Element {
+fn draw_board_labels(
+    x_range: Range<u8>,
+    y_range: Range<u8>,
+    top_offset: f64,
+    options: &RenderOptions,
+) -> Element {
     let (top_margin, _, _, left_margin) = get_margins(&options.label_sides);
     let transform = format!(
         "translate({}, {})",
         format_float(left_margin),
-        format_float(top_margin)
+        format_float(top_margin + top_offset)
     );
     let mut group_builder = Element::builder("g", NAMESPACE)
         .attr("id", "board-labels")
@@ -538,6 +758,43 @@ fn draw_board_labels(x_range: Range<u8>, y_range: Range<u8>, options: &RenderOpt
     group_builder.build()
 }
 
+/// Draws a marker on the stones of the last `count` moves, fading with their age.
+fn build_last_moves_group(
+    goban: &Goban,
+    options: &RenderOptions,
+    count: u64,
+    no_markup_points: &HashSet<(u8, u8)>,
+) -> Element {
+    let mut group_builder = Element::builder("g", NAMESPACE).attr("id", "last-moves");
+    for (age, stone) in get_last_moves(goban, count).into_iter().rev() {
+        if no_markup_points.contains(&(stone.x, stone.y)) {
+            continue;
+        }
+        let opacity = 1.0 - age as f64 / count as f64;
+        group_builder = group_builder.append(draw_last_move(stone, opacity, &options.style));
+    }
+    group_builder.build()
+}
+
+/// Returns the stones from the last `count` moves which are still on the board, along with how
+/// many moves ago they were played.
+fn get_last_moves(goban: &Goban, count: u64) -> Vec<(u64, Stone)> {
+    let stones: HashSet<Stone> = goban.stones().collect();
+    let moves: Vec<(u64, Stone)> = goban.moves().collect();
+    let last_move_number = match moves.last() {
+        Some((n, _)) => *n,
+        None => return vec![],
+    };
+    let mut seen = HashSet::new();
+    moves
+        .into_iter()
+        .rev()
+        .map(|(n, stone)| (last_move_number - n, stone))
+        .take_while(|&(age, _)| age < count)
+        .filter(|(_, stone)| seen.insert((stone.x, stone.y)) && stones.contains(stone))
+        .collect()
+}
+
 fn draw_repeated_stones(
     goban: &Goban,
     width: u8,
@@ -559,37 +816,7 @@ fn draw_repeated_stones(
         .attr("y", format_float(y))
         .attr("font-size", format_float(entry_height))
         .attr("fill", options.style.line_color()); // TODO: Evaluate this choice
-    let move_number_options = options.move_number_options.unwrap();
-    let repeated_moves: Vec<(u64, u64)> = {
-        let mut repeated_moves = Vec::new();
-        let mut seen_moves: HashMap<(u8, u8), u64> = HashMap::new();
-        for (n, stone) in goban.moves() {
-            match seen_moves.entry((stone.x, stone.y)) {
-                std::collections::hash_map::Entry::Occupied(mut entry) => {
-                    if *entry.get() < move_number_options.start {
-                        entry.insert(n);
-                    }
-                    let value = *entry.get();
-                    let in_range = value >= move_number_options.start
-                        && move_number_options
-                            .end
-                            .map(|end| value <= end)
-                            .unwrap_or(true);
-                    if n > value && in_range {
-                        repeated_moves.push((
-                            n + move_number_options.count_from - move_number_options.start,
-                            value + move_number_options.count_from - move_number_options.start,
-                        ));
-                    }
-                }
-                std::collections::hash_map::Entry::Vacant(entry) => {
-                    entry.insert(n);
-                }
-            }
-        }
-        repeated_moves.sort_unstable();
-        repeated_moves
-    };
+    let repeated_moves = repeated_moves(goban, options);
     if repeated_moves.is_empty() {
         return None;
     }
@@ -623,6 +850,91 @@ fn draw_repeated_stones(
     Some((group, rect_height))
 }
 
+fn draw_caption(
+    id: &str,
+    text: &str,
+    width: u8,
+    diagram_height: f64,
+    options: &RenderOptions,
+) -> (Element, f64) {
+    let (_, _, _, left_margin) = get_margins(&options.label_sides);
+    let font_size = options.style.caption_font_size();
+    let x = BOARD_MARGIN + left_margin + (f64::from(width) - 1.0) / 2.0;
+    let y = diagram_height + font_size;
+    let mut builder = Element::builder("text", NAMESPACE)
+        .attr("id", id)
+        .attr("x", format_float(x))
+        .attr("y", format_float(y))
+        .attr("text-anchor", "middle")
+        .attr("font-size", format_float(font_size))
+        .attr("fill", options.style.caption_color());
+    if let Some(font_family) = options.style.caption_font_family() {
+        builder = builder.attr("font-family", font_family);
+    }
+
+    (builder.append(text).build(), font_size)
+}
+
+/// Draws word wrapped lines of text spanning the width of the board.
+///
+/// Returns the element and its height, or `None` if there's no text to draw.
+fn draw_text_block(
+    id: &str,
+    lines: &[String],
+    centered: bool,
+    width: u8,
+    diagram_height: f64,
+    options: &RenderOptions,
+) -> Option<(Element, f64)> {
+    let (_, _, _, left_margin) = get_margins(&options.label_sides);
+    let font_size = options.style.caption_font_size();
+    let line_height = font_size * CAPTION_LINE_HEIGHT;
+    let block_width = f64::from(width) - 1.0;
+    let max_chars = (block_width / (font_size * CAPTION_CHAR_WIDTH)).floor() as usize;
+    let mut lines: Vec<String> = lines
+        .iter()
+        .flat_map(|line| wrap_text(line, max_chars))
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    let (x, anchor) = if centered {
+        (BOARD_MARGIN + left_margin + block_width / 2.0, "middle")
+    } else {
+        (BOARD_MARGIN + left_margin, "start")
+    };
+    let mut builder = Element::builder("text", NAMESPACE)
+        .attr("id", id)
+        .attr("y", format_float(diagram_height + font_size))
+        .attr("text-anchor", anchor)
+        .attr("font-size", format_float(font_size))
+        .attr("fill", options.style.caption_color());
+    if let Some(font_family) = options.style.caption_font_family() {
+        builder = builder.attr("font-family", font_family);
+    }
+    // Blank lines are drawn as extra space before the next line.
+    let mut dy = 0.0;
+    for line in &lines {
+        if !line.is_empty() {
+            let mut tspan_builder = Element::builder("tspan", NAMESPACE)
+                .attr("x", format_float(x))
+                .append(line.as_str());
+            if dy > 0.0 {
+                tspan_builder = tspan_builder.attr("dy", format_float(dy));
+            }
+            builder = builder.append(tspan_builder);
+            dy = 0.0;
+        }
+        dy += line_height;
+    }
+    let height = font_size + line_height * (lines.len() - 1) as f64;
+
+    Some((builder.build(), height))
+}
+
 fn draw_stone(stone: Stone, style: &GobanStyle) -> Element {
     let mut circle_builder = Element::builder("circle", NAMESPACE)
         .attr("cx", stone.x)
@@ -755,6 +1067,122 @@ fn draw_selected(x: u8, y: u8, color: Option<StoneColor>, style: &GobanStyle) ->
         .build()
 }
 
+fn draw_last_move(stone: Stone, opacity: f64, style: &GobanStyle) -> Element {
+    let (x, y, color) = (stone.x, stone.y, Some(stone.color));
+    let mut element = match style.last_move_marker() {
+        LastMoveMarker::Circle => draw_circle(x, y, color, style),
+        LastMoveMarker::Triangle => draw_triangle(x, y, color, style),
+        LastMoveMarker::Dot => Element::builder("circle", NAMESPACE)
+            .attr("fill", style.markup_color(color))
+            .attr("cx", x)
+            .attr("cy", y)
+            .attr("r", "0.15")
+            .build(),
+    };
+    if opacity < 1.0 {
+        element.set_attr("opacity", format_float(opacity));
+    }
+    element
+}
+
+fn draw_territory(x: u8, y: u8, color: StoneColor, style: &GobanStyle) -> Element {
+    let width = style.territory_size();
+    Element::builder("rect", NAMESPACE)
+        .attr("fill", style.territory_color(color))
+        .attr("x", format_float(f64::from(x) - 0.5 * width))
+        .attr("y", format_float(f64::from(y) - 0.5 * width))
+        .attr("width", format_float(width))
+        .attr("height", format_float(width))
+        .build()
+}
+
+fn draw_ownership(x: u8, y: u8, value: f64, style: &GobanStyle) -> Element {
+    let color = if value > 0.0 {
+        StoneColor::Black
+    } else {
+        StoneColor::White
+    };
+    let width = style.ownership_size();
+    Element::builder("rect", NAMESPACE)
+        .attr("fill", style.ownership_color(color))
+        .attr(
+            "fill-opacity",
+            format_float(value.abs() * style.ownership_opacity()),
+        )
+        .attr("x", format_float(f64::from(x) - 0.5 * width))
+        .attr("y", format_float(f64::from(y) - 0.5 * width))
+        .attr("width", format_float(width))
+        .attr("height", format_float(width))
+        .build()
+}
+
+fn draw_candidate(
+    x: u8,
+    y: u8,
+    lines: &[String],
+    color: Option<StoneColor>,
+    (fill, opacity): (&str, f64),
+    style: &GobanStyle,
+) -> Element {
+    let font_size = if lines.len() > 2 { 0.24 } else { 0.3 };
+    let mut text_builder = Element::builder("text", NAMESPACE)
+        .attr("font-size", format_float(font_size))
+        .attr("fill", style.markup_color(color));
+    // Center the lines vertically on the point.
+    let first_dy = 0.35 - 0.5 * (lines.len() as f64 - 1.0);
+    for (i, line) in lines.iter().enumerate() {
+        let dy = first_dy + i as f64;
+        text_builder = text_builder.append(
+            Element::builder("tspan", NAMESPACE)
+                .attr("x", x)
+                .attr("y", y)
+                .attr("dy", format!("{}em", format_float(dy)))
+                .append(line.as_str())
+                .build(),
+        );
+    }
+    let mut group_builder = Element::builder("g", NAMESPACE);
+    // A candidate on a stone (from stale analysis) is only labelled.
+    if color.is_none() {
+        group_builder = group_builder.append(
+            Element::builder("circle", NAMESPACE)
+                .attr("cx", x)
+                .attr("cy", y)
+                .attr("r", "0.48")
+                .attr("fill", fill)
+                .attr("fill-opacity", format_float(opacity)),
+        );
+    }
+    group_builder.append(text_builder.build()).build()
+}
+
+/// Draws the annotation text as a badge in the top right corner of the point.
+fn draw_annotation(x: u8, y: u8, text: &str, style: &GobanStyle) -> Element {
+    let height = ANNOTATION_FONT_SIZE * 1.3;
+    let width = height.max(ANNOTATION_FONT_SIZE * (0.4 + 0.6 * text.chars().count() as f64));
+    let right = f64::from(x) + 0.5;
+    let top = f64::from(y) - 0.5;
+    Element::builder("g", NAMESPACE)
+        .append(
+            Element::builder("rect", NAMESPACE)
+                .attr("fill", style.annotation_color())
+                .attr("x", format_float(right - width))
+                .attr("y", format_float(top))
+                .attr("width", format_float(width))
+                .attr("height", format_float(height))
+                .attr("rx", format_float(height / 2.0)),
+        )
+        .append(
+            Element::builder("text", NAMESPACE)
+                .attr("x", format_float(right - width / 2.0))
+                .attr("y", format_float(top + height / 2.0))
+                .attr("dy", "0.35em")
+                .attr("fill", "white")
+                .append(text),
+        )
+        .build()
+}
+
 fn dim_square(x: u8, y: u8) -> Element {
     Element::builder("g", NAMESPACE)
         .attr("stroke", "none")
@@ -819,6 +1247,22 @@ fn get_margins(label_sides: &BoardSideSet) -> (f64, f64, f64, f64) {
     (top, right, bottom, left)
 }
 
+/// Prefixes ids, and references to them, so several diagrams can share a document.
+pub(crate) fn prefix_ids(element: &mut Element, prefix: &str) {
+    for (name, value) in element.attrs_mut() {
+        if name == "id" {
+            *value = format!("{prefix}{value}");
+        } else if value.contains("url(#") {
+            *value = value.replace("url(#", &format!("url(#{prefix}"));
+        } else if name.ends_with("href") && value.starts_with('#') {
+            *value = format!("#{prefix}{}", &value[1..]);
+        }
+    }
+    for child in element.children_mut() {
+        prefix_ids(child, prefix);
+    }
+}
+
 fn format_float(x: f64) -> String {
     format!("{x:.4}")
         .trim_end_matches('0')
@@ -1,32 +1,77 @@
+use std::collections::HashMap;
+
 use super::options::BoardSide;
-use super::{board_label_text, RenderOptions};
+use super::{
+    board_label_text, caption_lines, diagram_stones, game_info_lines, get_move_numbers,
+    repeated_moves, RenderOptions,
+};
 
 use crate::errors::{GobanError, UsageError};
 use crate::goban::StoneColor;
 use crate::Goban;
 
 pub fn render(goban: &Goban, options: &RenderOptions) -> Result<String, GobanError> {
+    let transformed;
+    let goban = if options.transform.is_identity() {
+        goban
+    } else {
+        transformed = goban.transformed(&options.transform);
+        &transformed
+    };
     let (x_range, y_range) = options.goban_range.get_ranges(goban, options)?;
     let width = x_range.end - x_range.start;
     let height = y_range.end - y_range.start;
     if !options.label_sides.is_empty() && width > 25 || height > 99 {
         return Err(GobanError::UnlabellableRange);
     }
+    let cells = cells(goban, options)?;
+    // Move numbers need two columns per point.
+    let cell_width = if options.move_number_options.is_some() {
+        2
+    } else {
+        1
+    };
     let mut lines: Vec<String> = vec![];
+    if options.draw_game_info {
+        lines.extend(game_info_lines(goban));
+    }
     let label_padding = if options.label_sides.contains(BoardSide::West) {
         "   "
     } else {
         ""
     };
+    // Pad every cell but the last, unless there are labels on the east side.
+    let pad_cell = |x: u8, cell: String| -> String {
+        if x + 1 < x_range.end || options.label_sides.contains(BoardSide::East) {
+            format!("{cell:<cell_width$}")
+        } else {
+            cell
+        }
+    };
+    let column_labels = || -> String {
+        x_range
+            .clone()
+            .map(|x| pad_cell(x, board_label_text(x)))
+            .collect()
+    };
     if options.label_sides.contains(BoardSide::North) {
-        let line: String = x_range.clone().map(board_label_text).collect();
-        lines.push(format!("{label_padding}{line}"));
+        lines.push(format!("{label_padding}{}", column_labels()));
     }
     for y in y_range {
         let mut line = x_range
             .clone()
-            .map(|x| options.tileset.char_at(goban, x, y))
-            .collect();
+            .map(|x| {
+                let cell = cells
+                    .get(&(x, y))
+                    .cloned()
+                    .unwrap_or_else(|| options.tileset.char_at(goban, None, x, y).to_string());
+                // Only labels can be wider than a cell.
+                if cell.chars().count() > cell_width {
+                    return Err(GobanError::UnrepresentableLabel(cell));
+                }
+                Ok(pad_cell(x, cell))
+            })
+            .collect::<Result<String, _>>()?;
         if options.label_sides.contains(BoardSide::West) {
             line = format!("{: >2} {}", y + 1, line);
         }
@@ -36,22 +81,91 @@ pub fn render(goban: &Goban, options: &RenderOptions) -> Result<String, GobanErr
         lines.push(line);
     }
     if options.label_sides.contains(BoardSide::South) {
-        let line: String = x_range.clone().map(board_label_text).collect();
-        lines.push(format!("{label_padding}{line}"));
+        lines.push(format!("{label_padding}{}", column_labels()));
+    }
+    if options.kifu_mode {
+        let repeated_moves: Vec<String> = repeated_moves(goban, options)
+            .iter()
+            .map(|(move_num, original)| format!("{move_num}→{original}"))
+            .collect();
+        if !repeated_moves.is_empty() {
+            lines.push(repeated_moves.join(", "));
+        }
+    }
+    for (_, caption) in caption_lines(goban, options) {
+        lines.push(caption);
+    }
+    if options.draw_comment {
+        lines.extend(
+            goban
+                .comment()
+                .into_iter()
+                .flat_map(str::lines)
+                .map(str::to_string),
+        );
     }
     Ok(lines.join("\n"))
 }
 
+/// Returns the text to draw at each point with a stone, move number, label, or markup.
+fn cells(goban: &Goban, options: &RenderOptions) -> Result<HashMap<(u8, u8), String>, GobanError> {
+    let stones: HashMap<(u8, u8), StoneColor> = diagram_stones(goban, options)
+        .into_iter()
+        .map(|stone| ((stone.x, stone.y), stone.color))
+        .collect();
+    let mut cells: HashMap<(u8, u8), String> = stones
+        .iter()
+        .map(|(&(x, y), &color)| {
+            let tile = options.tileset.char_at(goban, Some(color), x, y);
+            ((x, y), tile.to_string())
+        })
+        .collect();
+    let markup = [
+        (options.draw_marks, goban.marks().collect::<Vec<_>>(), 0),
+        (options.draw_triangles, goban.triangles().collect(), 1),
+        (options.draw_circles, goban.circles().collect(), 2),
+        (options.draw_squares, goban.squares().collect(), 3),
+    ];
+    for (draw, points, index) in markup.iter() {
+        if !draw {
+            continue;
+        }
+        for point in points {
+            let c = options
+                .markup_tileset
+                .char_at(*index, stones.get(point).copied());
+            cells.insert(*point, c.to_string());
+        }
+    }
+    if options.draw_labels {
+        for (point, text) in goban.labels() {
+            cells.insert(*point, text.to_string());
+        }
+    }
+    if let Some(move_number_options) = options.move_number_options {
+        for (n, stone) in get_move_numbers(goban, options) {
+            let n = n + move_number_options.count_from - move_number_options.start;
+            if n >= 100 {
+                return Err(GobanError::UnrepresentableMoveNumbers(
+                    "Text diagrams can only number moves up to 99",
+                ));
+            }
+            cells.insert((stone.x, stone.y), n.to_string());
+        }
+    }
+    Ok(cells)
+}
+
 #[derive(Debug, Clone)]
 pub struct TileSet {
     tiles: [char; 11],
 }
 
 impl TileSet {
-    fn char_at(&self, goban: &Goban, x: u8, y: u8) -> char {
+    fn char_at(&self, goban: &Goban, color: Option<StoneColor>, x: u8, y: u8) -> char {
         let max_x = goban.size().0 - 1;
         let max_y = goban.size().1 - 1;
-        match goban.stone_color(x, y) {
+        match color {
             Some(StoneColor::White) => self.tiles[0],
             Some(StoneColor::Black) => self.tiles[1],
             None => match (x, y) {
@@ -90,12 +204,52 @@ impl Default for TileSet {
     }
 }
 
+/// Characters for marks, triangles, circles and squares on empty points, followed by the same
+/// four on white stones and then on black stones.
+#[derive(Debug, Clone)]
+pub struct MarkupTileSet {
+    tiles: [char; 12],
+}
+
+impl MarkupTileSet {
+    fn char_at(&self, markup_index: usize, color: Option<StoneColor>) -> char {
+        match color {
+            Some(StoneColor::White) => self.tiles[4 + markup_index],
+            Some(StoneColor::Black) => self.tiles[8 + markup_index],
+            None => self.tiles[markup_index],
+        }
+    }
+}
+
+impl std::str::FromStr for MarkupTileSet {
+    type Err = UsageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use std::convert::TryInto;
+
+        let tiles: [char; 12] = s
+            .chars()
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| UsageError::InvalidMarkupTileSet)?;
+        Ok(MarkupTileSet { tiles })
+    }
+}
+
+impl Default for MarkupTileSet {
+    fn default() -> Self {
+        "✕△◯□✖▲◉■⊗◬◎▣".parse().unwrap()
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default, clippy::needless_borrow)]
 mod tests {
     use std::path::PathBuf;
 
-    use crate::render::GobanRange;
-    use crate::{Goban, RenderOptions};
+    use crate::errors::GobanError;
+    use crate::render::{GobanRange, MoveNumberOptions};
+    use crate::{Goban, NodeDescription, NodeNumber, RenderOptions};
 
     use super::render;
 
@@ -231,4 +385,131 @@ OXXO...
         println!("{}", diagram);
         assert_eq!(diagram, expected);
     }
+
+    #[test]
+    fn markup() {
+        let options = RenderOptions {
+            node_description: NodeDescription {
+                node_number: NodeNumber::Number(2),
+                ..Default::default()
+            },
+            goban_range: GobanRange::Ranged(2..7, 2..10),
+            draw_marks: true,
+            draw_triangles: true,
+            draw_circles: true,
+            draw_squares: true,
+            ..Default::default()
+        };
+        let diagram = build_diagram("markup", &options);
+        let expected = "\
+┼┼┼┼┼
+┼◬△▲┼
+┼◬△▲┼
+┼◬△▲┼
+┼○┼┼┼
+┼⊗✕✖┼
+┼⊗✕✖┼
+┼⊗✕✖┼";
+        assert_eq!(diagram, expected);
+    }
+
+    #[test]
+    fn labels_markup() {
+        let options = RenderOptions {
+            node_description: NodeDescription {
+                node_number: NodeNumber::Number(3),
+                ..Default::default()
+            },
+            goban_range: GobanRange::Ranged(2..7, 2..4),
+            draw_labels: true,
+            ..Default::default()
+        };
+        let diagram = build_diagram("labels", &options);
+        let expected = "\
+┼1┼2┼
+┼○┼●┼";
+        assert_eq!(diagram, expected);
+    }
+
+    #[test]
+    fn long_labels() {
+        let options = RenderOptions {
+            draw_labels: true,
+            ..Default::default()
+        };
+        let goban = Goban::from_sgf("(;SZ[3]LB[aa:AB])", &Default::default(), true).unwrap();
+        assert!(matches!(
+            render(&goban, &options),
+            Err(GobanError::UnrepresentableLabel(label)) if label == "AB"
+        ));
+        let options = RenderOptions {
+            move_number_options: Some(MoveNumberOptions {
+                start: 1,
+                end: None,
+                count_from: 1,
+            }),
+            ..options
+        };
+        assert_eq!(render(&goban, &options).unwrap(), "AB┯ ┓\n┠ ┼ ┨\n┗ ┷ ┛");
+    }
+
+    #[test]
+    fn move_numbers() {
+        let options = RenderOptions {
+            goban_range: GobanRange::ShrinkWrap,
+            move_number_options: Some(MoveNumberOptions {
+                start: 1,
+                end: None,
+                count_from: 1,
+            }),
+            ..Default::default()
+        };
+        let diagram = build_diagram("prob45", &options);
+        let expected = "\
+┏ ┯ 1 5 2 ● ┯
+○ ○ ┼ ○ ○ ● ┼
+4 ┼ ○ ● ● ● ┼
+● 3 ○ ● ┼ ┼ ┼
+┠ ● ● ┼ ┼ ┼ ┼
+┠ ┼ ┼ ┼ ┼ ┼ ┼";
+        assert_eq!(diagram, expected);
+    }
+
+    #[test]
+    fn kifu() {
+        let options = RenderOptions {
+            move_number_options: Some(MoveNumberOptions {
+                start: 1,
+                end: None,
+                count_from: 1,
+            }),
+            kifu_mode: true,
+            ..Default::default()
+        };
+        let sgf = "(;SZ[4];B[ba];W[aa];B[ab];W[bb];B[aa])";
+        let goban = Goban::from_sgf(sgf, &options.node_description, true).unwrap();
+        let diagram = render(&goban, &options).unwrap();
+        let expected = "\
+2 1 ┯ ┓
+3 4 ┼ ┨
+┠ ┼ ┼ ┨
+┗ ┷ ┷ ┛
+5→2";
+        assert_eq!(diagram, expected);
+    }
+
+    #[test]
+    fn move_numbers_over_99() {
+        let options = RenderOptions {
+            move_number_options: Some(MoveNumberOptions {
+                start: 1,
+                end: None,
+                count_from: 99,
+            }),
+            ..Default::default()
+        };
+        let sgf = "(;SZ[4];B[ba];W[aa])";
+        let goban = Goban::from_sgf(sgf, &options.node_description, true).unwrap();
+        assert!(render(&goban, &options).is_err());
+    }
 }
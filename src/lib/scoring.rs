@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::goban::{Goban, StoneColor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ScoringRules {
+    #[default]
+    Japanese,
+    Chinese,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+    pub black: f64,
+    pub white: f64,
+    pub territory: HashMap<(u8, u8), StoneColor>,
+}
+
+impl Score {
+    /// Returns the result in SGF `RE` format (e.g. "B+3.5", or "Draw").
+    pub fn result(&self) -> String {
+        if self.black > self.white {
+            format!("B+{}", self.black - self.white)
+        } else if self.white > self.black {
+            format!("W+{}", self.white - self.black)
+        } else {
+            "Draw".to_string()
+        }
+    }
+}
+
+/// Scores the current position after removing the given dead stones.
+///
+/// Empty regions bordered only by one player's stones count as that player's territory. Komi is
+/// added to white's score.
+pub fn score(goban: &Goban, dead_stones: &HashSet<(u8, u8)>, rules: ScoringRules) -> Score {
+    let stones: HashMap<(u8, u8), StoneColor> = goban
+        .stones()
+        .map(|stone| ((stone.x, stone.y), stone.color))
+        .filter(|(point, _)| !dead_stones.contains(point))
+        .collect();
+    let territory = territory(goban, &stones);
+
+    let points = |color| -> f64 {
+        let territory = territory.values().filter(|&&c| c == color).count() as u64;
+        let bonus = match rules {
+            ScoringRules::Japanese => {
+                let dead = goban
+                    .stones()
+                    .filter(|s| s.color != color && dead_stones.contains(&(s.x, s.y)))
+                    .count() as u64;
+                goban.prisoners(color) + dead
+            }
+            ScoringRules::Chinese => stones.values().filter(|&&c| c == color).count() as u64,
+        };
+        (territory + bonus) as f64
+    };
+
+    Score {
+        black: points(StoneColor::Black),
+        white: points(StoneColor::White) + goban.komi().unwrap_or(0.0),
+        territory,
+    }
+}
+
+/// Finds the empty points surrounded only by stones of a single color.
+fn territory(
+    goban: &Goban,
+    stones: &HashMap<(u8, u8), StoneColor>,
+) -> HashMap<(u8, u8), StoneColor> {
+    let size = goban.size();
+    let mut territory = HashMap::new();
+    let mut seen = HashSet::new();
+    for x in 0..size.0 {
+        for y in 0..size.1 {
+            if seen.contains(&(x, y)) || stones.contains_key(&(x, y)) {
+                continue;
+            }
+            let mut region = vec![];
+            let mut borders = vec![];
+            let mut to_process = vec![(x, y)];
+            seen.insert((x, y));
+            while let Some(point) = to_process.pop() {
+                region.push(point);
+                for neighbor in goban.neighbors(point) {
+                    match stones.get(&neighbor) {
+                        Some(color) => {
+                            if !borders.contains(color) {
+                                borders.push(*color);
+                            }
+                        }
+                        None => {
+                            if seen.insert(neighbor) {
+                                to_process.push(neighbor);
+                            }
+                        }
+                    }
+                }
+            }
+            if let [color] = borders[..] {
+                territory.extend(region.into_iter().map(|point| (point, color)));
+            }
+        }
+    }
+    territory
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::goban::StoneColor;
+    use crate::Goban;
+
+    use super::{score, ScoringRules};
+
+    static TEST_DATA: &str =
+        "(;SZ[5]KM[0.5]AB[ca][cb][cc][cd][ce][ed]AW[da][db][dc][dd][de];W[bb])";
+
+    #[test]
+    fn japanese_score() {
+        let goban = Goban::from_sgf(TEST_DATA, &Default::default(), true).unwrap();
+        let dead_stones: HashSet<_> = [(1, 1), (4, 3)].iter().copied().collect();
+        let score = score(&goban, &dead_stones, ScoringRules::Japanese);
+        let count = |color| score.territory.values().filter(|&&c| c == color).count();
+        assert_eq!(count(StoneColor::Black), 10);
+        assert_eq!(count(StoneColor::White), 5);
+        assert_eq!(score.black, 11.0);
+        assert_eq!(score.white, 6.5);
+        assert_eq!(score.result(), "B+4.5");
+    }
+
+    #[test]
+    fn chinese_score() {
+        let goban = Goban::from_sgf(TEST_DATA, &Default::default(), true).unwrap();
+        let dead_stones: HashSet<_> = [(1, 1), (4, 3)].iter().copied().collect();
+        let score = score(&goban, &dead_stones, ScoringRules::Chinese);
+        assert_eq!(score.black, 15.0);
+        assert_eq!(score.white, 10.5);
+        assert_eq!(score.result(), "B+4.5");
+    }
+
+    #[test]
+    fn dame_is_not_territory() {
+        let goban = Goban::from_sgf("(;SZ[3]AB[aa]AW[cc])", &Default::default(), true).unwrap();
+        let score = score(&goban, &HashSet::new(), ScoringRules::Chinese);
+        assert!(score.territory.is_empty());
+        assert_eq!(score.result(), "Draw");
+    }
+}
@@ -1,25 +1,103 @@
 use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
 use minidom::Element;
+use sgf_parse::GameTree;
 
-use sgf_render::{Command, Goban, OutputFormat, QueryArgs, RenderOptions, SgfRenderArgs};
+use sgf_render::{
+    animation, grid, overlay, parse_collection, AnimationOptions, BookArgs, Command, Goban,
+    NodeDescription, OutputFormat, QueryArgs, RenderOptions, RuleCheckMode, SgfRenderArgs,
+    VariationOverlay,
+};
 
 fn main() {
     let parsed_args = SgfRenderArgs::parse();
-    let input = match read_input(&parsed_args.infile) {
-        Ok(goban) => goban,
+    match &parsed_args.command {
+        // Books read their own list of input files.
+        Some(Command::Book(book_args)) => book(&parsed_args.infile, book_args),
+        Some(Command::Query(query_args)) => {
+            query(&read_input_or_exit(&parsed_args.infile), query_args)
+        }
+        None => {
+            let input = read_input_or_exit(&parsed_args.infile);
+            render(&input, parsed_args)
+        }
+    }
+}
+
+fn read_input_or_exit(infile: &Option<PathBuf>) -> String {
+    match read_input(infile) {
+        Ok(input) => input,
         Err(e) => {
             eprintln!("Failed to read input: {e}");
             std::process::exit(1);
         }
+    }
+}
+
+fn book(infile: &Option<PathBuf>, book_args: &BookArgs) {
+    let options = match book_args.options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Failed to parse arguments: {e}");
+            std::process::exit(1);
+        }
+    };
+    // With no files at all, read a single collection from stdin.
+    let mut files: Vec<Option<&PathBuf>> = infile
+        .iter()
+        .chain(&book_args.more_files)
+        .map(Some)
+        .collect();
+    if files.is_empty() {
+        files.push(None);
+    }
+    let mut problems = vec![];
+    for file in files {
+        let name = file.map_or("stdin".into(), |path| path.display().to_string());
+        let input = match read_input(&file) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("Failed to read {name}: {e}");
+                std::process::exit(1);
+            }
+        };
+        match sgf_render::read_problems(&input, !book_args.lenient, &options) {
+            Ok(file_problems) => problems.extend(file_problems),
+            Err(e) => {
+                eprintln!("Failed to generate goban from {name}: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+    let pages = match sgf_render::render_book(&problems, &options) {
+        Ok(pages) => pages,
+        Err(e) => {
+            eprintln!("Failed to generate goban: {e}");
+            std::process::exit(1);
+        }
     };
+    if let Err(e) = write_book(&pages, &book_args.outfile) {
+        eprintln!("Failed to write output: {e}");
+        std::process::exit(1);
+    }
+}
 
-    match parsed_args.command {
-        Some(Command::Query(query_args)) => query(&input, &query_args),
-        None => render(&input, parsed_args),
+/// Writes the pages to a PDF, or as numbered SVGs in a directory.
+fn write_book(pages: &[Element], outfile: &Path) -> Result<(), Box<dyn Error>> {
+    if outfile.extension().is_some_and(|ext| ext == "pdf") {
+        #[cfg(feature = "pdf")]
+        return save_pdf(Box::new(std::fs::File::create(outfile)?), pages);
+        #[cfg(not(feature = "pdf"))]
+        return Err("PDF output requires the 'pdf' feature".into());
     }
+    std::fs::create_dir_all(outfile)?;
+    for (i, page) in pages.iter().enumerate() {
+        let mut file = std::fs::File::create(outfile.join(format!("page-{}.svg", i + 1)))?;
+        page.write_to(&mut file)?;
+    }
+    Ok(())
 }
 
 fn query(input: &str, query_args: &QueryArgs) {
@@ -37,13 +115,46 @@ fn render(input: &str, parsed_args: SgfRenderArgs) {
             std::process::exit(1);
         }
     };
-    let goban = match Goban::from_sgf(input, &options.node_description, !parsed_args.lenient) {
-        Ok(goban) => goban,
+    let batch_nodes = match parsed_args.batch_nodes() {
+        Ok(batch_nodes) => batch_nodes,
+        Err(e) => {
+            eprintln!("Failed to parse arguments: {e}");
+            std::process::exit(1);
+        }
+    };
+    let collection = match parse_collection(input, !parsed_args.lenient) {
+        Ok(collection) => collection,
+        Err(e) => {
+            eprintln!("Failed to parse SGF: {e}");
+            std::process::exit(1);
+        }
+    };
+    if !batch_nodes.is_empty() {
+        return render_batch(&collection, &parsed_args, &options, &batch_nodes);
+    }
+    let options = select_node(&collection, &parsed_args, options);
+    if parsed_args.output_format.is_animation() {
+        return render_animation(&collection, &parsed_args, &options);
+    }
+    if let Some(overlay) = &options.variation_overlay {
+        return render_overlay(&collection, &parsed_args, &options, overlay);
+    }
+    let node_descriptions = std::slice::from_ref(&options.node_description);
+    let goban = match Goban::from_collection(
+        &collection,
+        node_descriptions,
+        &parsed_args.goban_options(),
+    ) {
+        Ok(mut gobans) => gobans.pop().unwrap(),
         Err(e) => {
             eprintln!("Failed to generate goban: {e}");
             std::process::exit(1);
         }
     };
+    check_rules(std::slice::from_ref(&goban), parsed_args.check_rules);
+    if let Some(page_options) = options.kifu_page_options(&goban) {
+        return render_kifu_pages(&goban, &parsed_args, &page_options);
+    }
     if let Err(e) = write_output(
         &goban,
         &options,
@@ -55,6 +166,179 @@ fn render(input: &str, parsed_args: SgfRenderArgs) {
     }
 }
 
+/// Returns the options with the node found by the node selector, if there is one.
+fn select_node(
+    collection: &[GameTree],
+    parsed_args: &SgfRenderArgs,
+    options: RenderOptions,
+) -> RenderOptions {
+    let node_selector = match parsed_args.render_args.node_selector() {
+        Some(node_selector) => node_selector,
+        None => return options,
+    };
+    match node_selector.resolve(collection, &options.node_description) {
+        Ok(node_description) => RenderOptions {
+            node_description,
+            ..options
+        },
+        Err(e) => {
+            eprintln!("Failed to select node: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn render_batch(
+    collection: &[GameTree],
+    parsed_args: &SgfRenderArgs,
+    options: &RenderOptions,
+    batch_nodes: &[NodeDescription],
+) {
+    let gobans = match Goban::from_collection(collection, batch_nodes, &parsed_args.goban_options())
+    {
+        Ok(gobans) => gobans,
+        Err(e) => {
+            eprintln!("Failed to generate goban: {e}");
+            std::process::exit(1);
+        }
+    };
+    check_rules(&gobans, parsed_args.check_rules);
+    if parsed_args.grid {
+        let captions: Vec<String> = gobans
+            .iter()
+            .zip(batch_nodes)
+            .map(|(goban, node_description)| grid::caption(goban, node_description))
+            .collect();
+        let outfile = parsed_args.outfile.as_ref();
+        if let Err(e) = write_grid(&gobans, &captions, options, parsed_args, outfile) {
+            eprintln!("Failed to write output: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    for (goban, node_description) in gobans.iter().zip(batch_nodes) {
+        let options = RenderOptions {
+            node_description: *node_description,
+            ..options.clone()
+        };
+        let outfile = parsed_args.batch_outfile(node_description, None);
+        if let Err(e) = write_output(goban, &options, Some(&outfile), parsed_args.output_format) {
+            eprintln!("Failed to write {}: {e}", outfile.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+fn write_grid<P: AsRef<Path>>(
+    gobans: &[Goban],
+    captions: &[String],
+    options: &RenderOptions,
+    parsed_args: &SgfRenderArgs,
+    outfile: Option<P>,
+) -> Result<(), Box<dyn Error>> {
+    let svg = grid::render(gobans, captions, options, &parsed_args.grid_options())?;
+    let mut writer: Box<dyn std::io::Write> = match outfile {
+        Some(path) => Box::new(std::fs::File::create(path)?),
+        None => Box::new(std::io::stdout()),
+    };
+    match parsed_args.output_format {
+        OutputFormat::Svg => svg.write_to(&mut writer)?,
+        #[cfg(feature = "png")]
+        OutputFormat::Png => save_png(writer, &svg)?,
+        #[cfg(feature = "pdf")]
+        OutputFormat::Pdf => save_pdf(writer, std::slice::from_ref(&svg))?,
+        _ => return Err("Grids can only be written as SVG, PNG or PDF".into()),
+    }
+    Ok(())
+}
+
+fn render_animation(collection: &[GameTree], parsed_args: &SgfRenderArgs, options: &RenderOptions) {
+    let animation_options = parsed_args.animation_options();
+    let frames = match animation::frames(
+        collection,
+        &options.node_description,
+        &animation_options,
+        &parsed_args.goban_options(),
+    ) {
+        Ok(frames) => frames,
+        Err(e) => {
+            eprintln!("Failed to generate goban: {e}");
+            std::process::exit(1);
+        }
+    };
+    check_rules(&frames[frames.len() - 1..], parsed_args.check_rules);
+    if let Err(e) = write_animation(
+        &frames,
+        options,
+        &animation_options,
+        parsed_args.outfile.as_ref(),
+        parsed_args.output_format,
+    ) {
+        eprintln!("Failed to write output: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn render_overlay(
+    collection: &[GameTree],
+    parsed_args: &SgfRenderArgs,
+    options: &RenderOptions,
+    variation_overlay: &VariationOverlay,
+) {
+    let goban_options = parsed_args.goban_options();
+    let (goban, options) =
+        match overlay::overlay(collection, options, variation_overlay, &goban_options) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("Failed to generate goban: {e}");
+                std::process::exit(1);
+            }
+        };
+    check_rules(std::slice::from_ref(&goban), parsed_args.check_rules);
+    if let Err(e) = write_output(
+        &goban,
+        &options,
+        parsed_args.outfile.as_ref(),
+        parsed_args.output_format,
+    ) {
+        eprintln!("Failed to write output: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn render_kifu_pages(goban: &Goban, parsed_args: &SgfRenderArgs, page_options: &[RenderOptions]) {
+    for (i, options) in page_options.iter().enumerate() {
+        let outfile = parsed_args.batch_outfile(&options.node_description, Some(i + 1));
+        if let Err(e) = write_output(goban, options, Some(&outfile), parsed_args.output_format) {
+            eprintln!("Failed to write {}: {e}", outfile.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+fn check_rules(gobans: &[Goban], mode: RuleCheckMode) {
+    match mode {
+        RuleCheckMode::Off => {}
+        RuleCheckMode::Warn => {
+            let mut violations = vec![];
+            for violation in gobans.iter().flat_map(|goban| goban.rule_violations()) {
+                if !violations.contains(&violation) {
+                    eprintln!("Warning: {violation}");
+                    violations.push(violation);
+                }
+            }
+        }
+        RuleCheckMode::Error => {
+            for goban in gobans {
+                if let Err(e) = goban.check_rules() {
+                    eprintln!("Failed to generate goban: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
 fn read_input<P: AsRef<Path>>(infile: &Option<P>) -> Result<String, Box<dyn Error>> {
     let mut reader: Box<dyn std::io::Read> = match infile {
         Some(filename) => Box::new(std::io::BufReader::new(std::fs::File::open(filename)?)),
@@ -84,24 +368,51 @@ fn write_output<P: AsRef<Path>>(
             let diagram = sgf_render::text::render(goban, options)?;
             writeln!(writer, "{diagram}")?
         }
+        OutputFormat::Sensei => {
+            let diagram = sgf_render::sensei::render(goban, options)?;
+            writeln!(writer, "{diagram}")?
+        }
+        OutputFormat::Latex => {
+            let diagram = sgf_render::latex::render(goban, options)?;
+            writeln!(writer, "{diagram}")?
+        }
+        OutputFormat::Html => {
+            let html = sgf_render::html::render(std::slice::from_ref(goban), options)?;
+            writeln!(writer, "{html}")?
+        }
+        OutputFormat::AnimatedSvg => {
+            let frames = std::slice::from_ref(goban);
+            let svg = animation::render(frames, options, &AnimationOptions::default())?;
+            svg.write_to(&mut writer)?
+        }
         #[cfg(feature = "png")]
         OutputFormat::Png => {
             let svg = sgf_render::svg::render(goban, options)?;
             save_png(writer, &svg)?
         }
+        #[cfg(feature = "png")]
+        OutputFormat::Apng | OutputFormat::Gif => {
+            let frames = std::slice::from_ref(goban);
+            save_raster_animation(
+                writer,
+                frames,
+                options,
+                &AnimationOptions::default(),
+                format,
+            )?
+        }
+        #[cfg(feature = "pdf")]
+        OutputFormat::Pdf => {
+            let svg = sgf_render::svg::render(goban, options)?;
+            save_pdf(writer, std::slice::from_ref(&svg))?
+        }
     }
     Ok(())
 }
 
 #[cfg(feature = "png")]
 fn save_png(mut writer: Box<dyn std::io::Write>, svg: &Element) -> Result<(), Box<dyn Error>> {
-    let tree = {
-        let mut buffer: Vec<u8> = vec![];
-        svg.write_to(&mut buffer)?;
-        let mut fontdb = usvg::fontdb::Database::new();
-        fontdb.load_font_data(include_bytes!("../resources/Inter-Bold.ttf").to_vec());
-        usvg::Tree::from_data(&buffer, &usvg::Options::default(), &fontdb)?
-    };
+    let tree = usvg_tree(svg)?;
     let data = {
         let pixmap_size = tree.size().to_int_size();
         let mut pixmap = tiny_skia::Pixmap::new(pixmap_size.width(), pixmap_size.height()).unwrap();
@@ -112,3 +423,146 @@ fn save_png(mut writer: Box<dyn std::io::Write>, svg: &Element) -> Result<(), Bo
     writer.write_all(&data)?;
     Ok(())
 }
+
+/// Writes the frames as an animated SVG, PNG or GIF.
+fn write_animation<P: AsRef<Path>>(
+    frames: &[Goban],
+    options: &RenderOptions,
+    animation_options: &AnimationOptions,
+    outfile: Option<P>,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer: Box<dyn std::io::Write> = match outfile {
+        Some(path) => Box::new(std::fs::File::create(path)?),
+        None => Box::new(std::io::stdout()),
+    };
+    match format {
+        #[cfg(feature = "png")]
+        OutputFormat::Apng | OutputFormat::Gif => {
+            save_raster_animation(writer, frames, options, animation_options, format)?
+        }
+        OutputFormat::Html => {
+            let html = sgf_render::html::render(frames, options)?;
+            writeln!(writer, "{html}")?
+        }
+        _ => {
+            let svg = animation::render(frames, options, animation_options)?;
+            svg.write_to(&mut writer)?
+        }
+    }
+    Ok(())
+}
+
+/// Writes the frames as an animated PNG or GIF.
+#[cfg(feature = "png")]
+fn save_raster_animation(
+    writer: Box<dyn std::io::Write>,
+    frames: &[Goban],
+    options: &RenderOptions,
+    animation_options: &AnimationOptions,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let options = animation::frame_options(frames, options)?;
+    let mut trees = vec![];
+    for frame in frames {
+        trees.push(usvg_tree(&sgf_render::svg::render(frame, &options)?)?);
+    }
+    // Frames can differ in height when their captions wrap to a different number of lines.
+    let width = trees.iter().map(|tree| tree.size().to_int_size().width());
+    let height = trees.iter().map(|tree| tree.size().to_int_size().height());
+    let (width, height) = (width.max().unwrap_or(1), height.max().unwrap_or(1));
+    let images: Vec<Vec<u8>> = trees
+        .iter()
+        .map(|tree| {
+            let mut pixmap = tiny_skia::Pixmap::new(width, height).unwrap();
+            resvg::render(tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+            pixmap
+                .pixels()
+                .iter()
+                .flat_map(|pixel| {
+                    let color = pixel.demultiply();
+                    vec![color.red(), color.green(), color.blue(), color.alpha()]
+                })
+                .collect()
+        })
+        .collect();
+
+    if format == OutputFormat::Gif {
+        let (width, height) = (width as u16, height as u16);
+        let mut encoder = gif::Encoder::new(writer, width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for mut image in images {
+            let mut frame = gif::Frame::from_rgba_speed(width, height, &mut image, 10);
+            // GIF delays are in hundredths of a second.
+            frame.delay = animation_options.frame_delay / 10;
+            encoder.write_frame(&frame)?;
+        }
+    } else {
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(images.len() as u32, 0)?;
+        encoder.set_frame_delay(animation_options.frame_delay, 1000)?;
+        let mut png_writer = encoder.write_header()?;
+        for image in images {
+            png_writer.write_image_data(&image)?;
+        }
+        png_writer.finish()?;
+    }
+    Ok(())
+}
+
+/// Writes each svg as a page of a single PDF.
+#[cfg(feature = "pdf")]
+fn save_pdf(mut writer: Box<dyn std::io::Write>, pages: &[Element]) -> Result<(), Box<dyn Error>> {
+    use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref};
+
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let page_tree_id = alloc.bump();
+    let mut pdf = Pdf::new();
+    let mut page_ids = vec![];
+    for svg in pages {
+        let tree = usvg_tree(svg)?;
+        let (chunk, svg_id) = svg2pdf::to_chunk(&tree, Default::default());
+        let mut ids = std::collections::HashMap::new();
+        let chunk = chunk.renumber(|old| *ids.entry(old).or_insert_with(|| alloc.bump()));
+        let svg_id = ids[&svg_id];
+        let page_id = alloc.bump();
+        let content_id = alloc.bump();
+        let (width, height) = (tree.size().width(), tree.size().height());
+
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, width, height));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        page.resources().x_objects().pair(Name(b"S1"), svg_id);
+        page.finish();
+        let mut content = Content::new();
+        content
+            .transform([width, 0.0, 0.0, height, 0.0, 0.0])
+            .x_object(Name(b"S1"));
+        pdf.stream(content_id, &content.finish());
+        pdf.extend(&chunk);
+        page_ids.push(page_id);
+    }
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .count(page_ids.len() as i32)
+        .kids(page_ids);
+
+    writer.write_all(&pdf.finish())?;
+    Ok(())
+}
+
+/// Parses the rendered svg with the embedded font available for text.
+#[cfg(any(feature = "png", feature = "pdf"))]
+fn usvg_tree(svg: &Element) -> Result<usvg::Tree, Box<dyn Error>> {
+    let mut buffer: Vec<u8> = vec![];
+    svg.write_to(&mut buffer)?;
+    let mut options = usvg::Options::default();
+    options
+        .fontdb_mut()
+        .load_font_data(include_bytes!("../resources/Inter-Bold.ttf").to_vec());
+    Ok(usvg::Tree::from_data(&buffer, &options)?)
+}